@@ -0,0 +1,427 @@
+use clap::{Args, Subcommand, ValueEnum};
+use repo_weaver_core::answers::{AnswerOrigin, AnswerStore};
+use repo_weaver_core::config::{AppConfig, AppLayer, ModuleManifest, WeaverConfig};
+use repo_weaver_core::input::InputSpec;
+use repo_weaver_core::interpolate;
+use repo_weaver_core::lockfile::Lockfile;
+use repo_weaver_core::module::ModuleResolver;
+use serde::Serialize;
+use serde_yml::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::warn;
+
+#[derive(Args)]
+pub struct AnswersArgs {
+    #[command(subcommand)]
+    pub command: AnswersCommand,
+}
+
+#[derive(Subcommand)]
+pub enum AnswersCommand {
+    /// List the inputs of each app with their value and where it comes from
+    List {
+        /// Only list this app
+        app: Option<String>,
+    },
+    /// Print the saved answer of an input
+    Get {
+        #[command(flatten)]
+        target: InputTarget,
+        /// Print the answer of a sensitive input, decrypted, instead of `***`
+        #[arg(long)]
+        include_sensitive: bool,
+    },
+    /// Save an answer, checked against the input's type and validation rules
+    Set {
+        #[command(flatten)]
+        target: InputTarget,
+        /// Value, written as at the prompt (`a,b` for lists, `{k: v}` for maps)
+        value: String,
+    },
+    /// Delete a saved answer, so the input is asked for again
+    Unset(InputTarget),
+    /// Print the saved answers of all apps (or one)
+    Export {
+        /// Only export this app
+        app: Option<String>,
+        #[arg(long, value_enum, default_value = "yaml")]
+        format: ExportFormat,
+        /// Include answers of sensitive inputs, decrypted
+        #[arg(long)]
+        include_sensitive: bool,
+    },
+}
+
+#[derive(Args)]
+pub struct InputTarget {
+    /// App name
+    pub app: String,
+    /// Input name
+    pub input: String,
+    /// Module reference of the app, when several of its modules declare the input
+    #[arg(long)]
+    pub module: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Yaml,
+}
+
+/// Where the value an input would get on the next apply comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Source {
+    /// `inputs` in weaver.yaml
+    Config,
+    /// The input's `from_command`, run on apply
+    Command,
+    /// Saved answer entered at a prompt
+    Prompted,
+    /// Saved answer from `rw answers set`
+    Set,
+    /// Saved computed default
+    Computed,
+    /// Saved answer of unknown origin, e.g. adopted from the legacy flat file
+    Answer,
+    /// Manifest default
+    Default,
+    /// Nothing yet, asked for on apply
+    Unset,
+}
+
+impl Source {
+    fn label(self) -> &'static str {
+        match self {
+            Source::Config => "explicit config",
+            Source::Command => "from command",
+            Source::Prompted => "prompted",
+            Source::Set => "set with rw answers",
+            Source::Computed => "computed default",
+            Source::Answer => "saved answer",
+            Source::Default => "manifest default",
+            Source::Unset => "unset (asked on apply)",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Entry {
+    app: String,
+    module: String,
+    input: String,
+    value: Option<Value>,
+    source: Source,
+}
+
+/// An app's layers with their manifests, as resolved by apply.
+struct Layers<'a> {
+    app: &'a AppConfig,
+    layers: Vec<(AppLayer, ModuleManifest)>,
+}
+
+pub fn run(args: AnswersArgs, json: bool) -> anyhow::Result<()> {
+    let config_path = Path::new("weaver.yaml");
+    if !config_path.exists() {
+        anyhow::bail!("weaver.yaml not found");
+    }
+    let config = WeaverConfig::load(config_path)?;
+    let mut store = AnswerStore::open(Path::new(".rw"))?;
+
+    match args.command {
+        AnswersCommand::List { app } => {
+            let resolver = ModuleResolver::new(Lockfile::load(Path::new("weaver.lock"))?)?;
+            let mut entries = Vec::new();
+            for app_config in select_apps(&config, app.as_deref())? {
+                let app = load_layers(&config, &resolver, app_config)?;
+                entries.extend(list_entries(&app, &mut store));
+            }
+            print_entries(&entries, json)
+        }
+        AnswersCommand::Get {
+            target,
+            include_sensitive,
+        } => {
+            let resolver = ModuleResolver::new(Lockfile::load(Path::new("weaver.lock"))?)?;
+            let app = load_layers(&config, &resolver, find_app(&config, &target.app)?)?;
+            let (layer, manifest) = app.target_layer(&target)?;
+            let saved = store.saved_layer(&target.app, &layer.reference, &manifest.inputs);
+            let value = saved.get(&target.input).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "App '{}' has no saved answer for '{}'",
+                    target.app,
+                    target.input
+                )
+            })?;
+            let sensitive = manifest.inputs[&target.input].sensitive
+                || store.is_sensitive(&target.app, &layer.reference, &target.input);
+            let value = if !sensitive {
+                value.clone()
+            } else if include_sensitive {
+                store.decrypted(value).map_err(|e| {
+                    anyhow::anyhow!("Answer '{}' of app '{}': {}", target.input, target.app, e)
+                })?
+            } else {
+                Value::from("***")
+            };
+            println!("{}", value_text(&value));
+            Ok(())
+        }
+        AnswersCommand::Set { target, value } => {
+            let resolver = ModuleResolver::new(Lockfile::load(Path::new("weaver.lock"))?)?;
+            let app = load_layers(&config, &resolver, find_app(&config, &target.app)?)?;
+            let (layer, manifest) = app.target_layer(&target)?;
+            let def = &manifest.inputs[&target.input];
+            let value = InputSpec::of(def)
+                .and_then(|spec| spec.parse_text(&value))
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "App '{}': input '{}' of module '{}': {}",
+                        target.app,
+                        target.input,
+                        layer.reference,
+                        e
+                    )
+                })?;
+
+            store.saved_layer(&target.app, &layer.reference, &manifest.inputs);
+            store.record(
+                &target.app,
+                &layer.reference,
+                [(target.input.clone(), value)].into(),
+                AnswerOrigin::Set,
+            );
+            store.save(false)?;
+            if layer.inputs.contains_key(&target.input) {
+                warn!(
+                    "weaver.yaml sets '{}' for app '{}', which takes precedence over the saved answer.",
+                    target.input, target.app
+                );
+            } else if !def.from_command.is_empty() {
+                warn!(
+                    "'{}' is derived from a command, which takes precedence over the saved answer.",
+                    target.input
+                );
+            }
+            Ok(())
+        }
+        AnswersCommand::Unset(target) => {
+            let resolver = ModuleResolver::new(Lockfile::load(Path::new("weaver.lock"))?)?;
+            let app = load_layers(&config, &resolver, find_app(&config, &target.app)?)?;
+            let (layer, manifest) = app.target_layer(&target)?;
+            let saved = store.saved_layer(&target.app, &layer.reference, &manifest.inputs);
+            if !saved.contains_key(&target.input) {
+                anyhow::bail!(
+                    "App '{}' has no saved answer for '{}'",
+                    target.app,
+                    target.input
+                );
+            }
+            store.remove(
+                &target.app,
+                &[(layer.reference.clone(), target.input.clone())],
+            );
+            store.save(false)?;
+            Ok(())
+        }
+        AnswersCommand::Export {
+            app,
+            format,
+            include_sensitive,
+        } => {
+            let mut exported: BTreeMap<String, BTreeMap<String, BTreeMap<String, Value>>> =
+                BTreeMap::new();
+            for (name, answers) in store.apps().clone() {
+                if app.as_deref().is_some_and(|app| app != name) {
+                    continue;
+                }
+                for (module, layer) in answers {
+                    for (key, value) in layer {
+                        let value = if !store.is_sensitive(&name, &module, &key) {
+                            value
+                        } else if include_sensitive {
+                            store.decrypted(&value).map_err(|e| {
+                                anyhow::anyhow!("Answer '{}' of app '{}': {}", key, name, e)
+                            })?
+                        } else {
+                            continue;
+                        };
+                        exported
+                            .entry(name.clone())
+                            .or_default()
+                            .entry(module.clone())
+                            .or_default()
+                            .insert(key, value);
+                    }
+                }
+            }
+            match format {
+                ExportFormat::Json => println!("{}", serde_json::to_string_pretty(&exported)?),
+                ExportFormat::Yaml => print!("{}", serde_yml::to_string(&exported)?),
+            }
+            Ok(())
+        }
+    }
+}
+
+fn find_app<'a>(config: &'a WeaverConfig, name: &str) -> anyhow::Result<&'a AppConfig> {
+    config
+        .apps
+        .iter()
+        .find(|a| a.name == name)
+        .ok_or_else(|| anyhow::anyhow!("App '{}' not found", name))
+}
+
+fn select_apps<'a>(
+    config: &'a WeaverConfig,
+    app: Option<&str>,
+) -> anyhow::Result<Vec<&'a AppConfig>> {
+    match app {
+        Some(name) => Ok(vec![find_app(config, name)?]),
+        None => Ok(config.apps.iter().collect()),
+    }
+}
+
+fn load_layers<'a>(
+    config: &WeaverConfig,
+    resolver: &ModuleResolver,
+    app: &'a AppConfig,
+) -> anyhow::Result<Layers<'a>> {
+    let mut layers = Vec::new();
+    for layer in app.layers()? {
+        let (module, _) = config.find_module(&layer.reference)?;
+        let path = resolver.resolve(module)?.path;
+        let manifest = ModuleManifest::load(&path.join("weaver.module.yaml"))?;
+        layers.push((layer, manifest));
+    }
+    Ok(Layers { app, layers })
+}
+
+impl Layers<'_> {
+    /// The layer whose module declares the target input.
+    fn target_layer(&self, target: &InputTarget) -> anyhow::Result<&(AppLayer, ModuleManifest)> {
+        let declaring: Vec<&(AppLayer, ModuleManifest)> = self
+            .layers
+            .iter()
+            .filter(|(layer, manifest)| {
+                manifest.inputs.contains_key(&target.input)
+                    && target
+                        .module
+                        .as_ref()
+                        .is_none_or(|module| *module == layer.reference)
+            })
+            .collect();
+        match declaring.as_slice() {
+            [single] => Ok(single),
+            [] => match &target.module {
+                Some(module) => anyhow::bail!(
+                    "Module '{}' of app '{}' has no input '{}'",
+                    module,
+                    self.app.name,
+                    target.input
+                ),
+                None => anyhow::bail!("App '{}' has no input '{}'", self.app.name, target.input),
+            },
+            several => anyhow::bail!(
+                "Input '{}' is declared by several modules of app '{}' ({}); pick one with --module",
+                target.input,
+                self.app.name,
+                several
+                    .iter()
+                    .map(|(layer, _)| layer.reference.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+fn list_entries(app: &Layers, store: &mut AnswerStore) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for (layer, manifest) in &app.layers {
+        let saved = store.saved_layer(&app.app.name, &layer.reference, &manifest.inputs);
+        for (key, def) in &manifest.inputs {
+            let (value, source) = if let Some(value) = layer.inputs.get(key) {
+                let value = if interpolate::contains_secret(value) {
+                    Value::from("***")
+                } else {
+                    value.clone()
+                };
+                (Some(value), Source::Config)
+            } else if !def.from_command.is_empty() {
+                (None, Source::Command)
+            } else if let Some(value) = saved.get(key) {
+                let source = match store.origin(&app.app.name, &layer.reference, key) {
+                    Some(AnswerOrigin::Prompt) => Source::Prompted,
+                    Some(AnswerOrigin::Set) => Source::Set,
+                    Some(AnswerOrigin::Computed) => Source::Computed,
+                    None => Source::Answer,
+                };
+                (Some(value.clone()), source)
+            } else if let Some(default) = &def.default {
+                (Some(default.clone()), Source::Default)
+            } else {
+                (None, Source::Unset)
+            };
+            // Sensitive values are never printed
+            let value = match value {
+                Some(_) if def.sensitive => Some(Value::from("***")),
+                value => value,
+            };
+            entries.push(Entry {
+                app: app.app.name.clone(),
+                module: layer.reference.clone(),
+                input: key.clone(),
+                value,
+                source,
+            });
+        }
+    }
+    entries
+}
+
+fn print_entries(entries: &[Entry], json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(entries)?);
+        return Ok(());
+    }
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|entry| {
+            [
+                entry.app.clone(),
+                entry.module.clone(),
+                entry.input.clone(),
+                entry.value.as_ref().map(value_text).unwrap_or_default(),
+                entry.source.label().to_string(),
+            ]
+        })
+        .collect();
+    let header = ["APP", "MODULE", "INPUT", "VALUE", "SOURCE"].map(String::from);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+/// A value as typed at a prompt: strings as is, anything else as JSON.
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
@@ -1,13 +1,22 @@
-use clap::Args;
+use crate::vars::{VarArgs, Vars};
+use clap::{Args, ValueEnum};
+use repo_weaver_core::answers::{AnswerOrigin, AnswerStore};
 use repo_weaver_core::app::App;
-use repo_weaver_core::config::{ModuleManifest, WeaverConfig};
+use repo_weaver_core::config::{AppConfig, FileRule, ModuleManifest, WeaverConfig};
+use repo_weaver_core::input::InputSpec;
+use repo_weaver_core::interpolate;
+use repo_weaver_core::lockfile::Lockfile;
 use repo_weaver_core::module::ModuleResolver;
+use repo_weaver_core::secret::SecretResolver;
 use repo_weaver_core::state::{
     FileState, State, calculate_checksum, calculate_checksum_from_bytes,
 };
-use repo_weaver_core::template::TemplateEngine;
+use repo_weaver_core::template::{self, TemplateContext, TemplateEngine};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
-use tracing::info;
+use std::rc::Rc;
+use tracing::{info, warn};
 use walkdir::WalkDir;
 
 #[derive(Args, Clone)]
@@ -19,6 +28,52 @@ pub struct ApplyArgs {
     /// Conflict resolution strategy
     #[arg(long, default_value = "stop")]
     pub strategy: String, // Parsing enum later
+
+    /// What to do with saved answers that match no current module input
+    #[arg(long, value_enum, default_value = "ask")]
+    pub stale_answers: StaleAnswers,
+
+    #[command(flatten)]
+    pub vars: VarArgs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum StaleAnswers {
+    /// Ask once per app (keep them when not interactive)
+    Ask,
+    /// Keep them and print a warning
+    Keep,
+    /// Delete them
+    Prune,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileKind {
+    /// Static file from the module's `files/` folder
+    Copy,
+    /// Template from the module's `templates/` folder
+    Template,
+}
+
+/// A file one of the app's modules wants to write.
+struct PlannedFile {
+    source: PathBuf,
+    /// The module's `files/` or `templates/` folder holding the source
+    root: PathBuf,
+    dest: PathBuf,
+    kind: FileKind,
+    /// Module reference (from the app's layers) that produced the file
+    layer: String,
+    /// Source as named in errors: the layer followed by the module-relative path
+    origin: String,
+    /// Values templates are rendered with
+    context: Rc<tera::Context>,
+}
+
+/// A module file left out for this app, listed by plan and apply.
+struct SkippedFile {
+    origin: String,
+    reason: String,
 }
 
 pub async fn run(args: ApplyArgs) -> anyhow::Result<()> {
@@ -38,210 +93,255 @@ pub async fn execute(args: ApplyArgs, dry_run: bool) -> anyhow::Result<()> {
     if !config_path.exists() {
         anyhow::bail!("weaver.yaml not found");
     }
+    let lockfile_path = Path::new("weaver.lock");
+    let resolver = ModuleResolver::new(Lockfile::load(lockfile_path)?)?;
+    crate::commands::validate::check_before_run(config_path, &resolver)?;
     let config = WeaverConfig::load(config_path)?;
+    let secrets = SecretResolver::for_workspace(Path::new("."), config.secrets.clone());
+
+    // Secret values must not leak through error messages
+    weave(&args, dry_run, &config, &resolver, &secrets)
+        .map_err(|e| anyhow::anyhow!("{}", secrets.mask(&format!("{:#}", e))))
+}
+
+fn weave(
+    args: &ApplyArgs,
+    dry_run: bool,
+    config: &WeaverConfig,
+    resolver: &ModuleResolver,
+    secrets: &SecretResolver,
+) -> anyhow::Result<()> {
+    // Every declared secret must resolve, templates see them as `secrets.<name>`
+    let resolved_secrets: BTreeMap<String, String> = secrets
+        .resolve_all()?
+        .into_iter()
+        .map(|(name, secret)| (name, secret.expose().clone()))
+        .collect();
+
+    let workspace_root = std::env::current_dir()?;
+    let lockfile_path = Path::new("weaver.lock");
+    let vars = Vars::load(&args.vars, config)?;
+    // Prompts need a terminal; CI runs are never interactive
+    let interactive = !args.vars.no_input && std::io::stdin().is_terminal();
 
     // Load State
     let state_path = Path::new(".rw/state.yaml");
     let mut state = State::load(state_path)?;
 
     // 2. Init components
-    let resolver = ModuleResolver::new(None)?;
-    let _template_engine = TemplateEngine::new()?;
-    let tera_context = tera::Context::new();
+    let mut template_engine = TemplateEngine::new()?;
+
+    let mut answers = AnswerStore::open(Path::new(".rw"))?;
+
+    // Modules actually used, written to weaver.lock on apply
+    let mut lockfile = Lockfile::default();
+
+    // Inputs left without a value, reported together before any file is written
+    let mut missing = Vec::new();
+    let mut apps = Vec::new();
 
     // 3. Process Apps
     for app_config in &config.apps {
         info!("Processing app: {}", app_config.name);
 
-        let module_config = config
-            .modules
-            .iter()
-            .find(|m| m.name == app_config.module)
-            .ok_or_else(|| anyhow::anyhow!("Module '{}' not found", app_config.module))?;
-
-        let module_path = resolver.resolve(&module_config.source, &module_config.r#ref)?;
-        let manifest_path = module_path.join("weaver.module.yaml");
-        let manifest = ModuleManifest::load(&manifest_path)?;
-
-        // Resolve missing inputs (Interactive)
-        let answers_path = Path::new(".rw/answers.yaml");
-        let resolved_inputs = crate::prompts::resolve_missing_inputs(
-            &manifest,
-            &app_config.inputs,
-            !args.auto_approve, // Interactive if not auto-approve?
-            // Spec says "Interactive Prompts". Usually implied by TTY access.
-            // But auto_approve flag usually refers to confirmation.
-            // Let's assume always interactive unless auto-approve is specified?
-            // Or maybe separate --no-input flag?
-            // "System checks... if interactive... prompt".
-            // auto_approve skips "Review Plan? [y/N]".
-            // Missing variables should probably block even in auto-approve unless defaults exist.
-            // If auto-approve is on, we are likely non-interactive.
-            // Let's allow prompting unless auto_approve is true (non-interactive mode).
-            &answers_path,
-        )?;
-
-        // Merge resolved inputs into config clone
-        let mut app_config_resolved = app_config.clone();
-        app_config_resolved.inputs.extend(resolved_inputs);
-
-        let app = App::instantiate(&app_config_resolved, &manifest)?;
-        let dest_root = PathBuf::from(&app.path);
-
-        // Files Processing
-        let files_src = module_path.join("files");
-        if files_src.exists() {
-            for entry in WalkDir::new(&files_src) {
-                let entry = entry?;
-                if entry.file_type().is_file() {
-                    let rel_path = entry.path().strip_prefix(&files_src)?;
-                    let dest_path = dest_root.join(rel_path);
-
-                    // Check Drift
-                    if dest_path.exists() {
-                        let current_chk = calculate_checksum(&dest_path)?;
-                        if let Some(file_state) = state.files.get(&dest_path) {
-                            if file_state.checksum != current_chk {
-                                // Drift detected
-                                if args.strategy == "stop" && !args.auto_approve {
-                                    if dry_run {
-                                        info!(
-                                            "Drift detected for {:?}. Plan would fail.",
-                                            dest_path
-                                        );
-                                    }
-                                    anyhow::bail!(
-                                        "Drift detected for {:?}. Use --strategy overwrite to force.",
-                                        dest_path
-                                    );
-                                } else {
-                                    if dry_run {
-                                        info!(
-                                            "Drift detected for {:?}. Plan would overwrite.",
-                                            dest_path
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    }
+        let layers = app_config.layers()?;
+        if layers.is_empty() {
+            anyhow::bail!(
+                "App '{}' must declare 'module' or 'extends'",
+                app_config.name
+            );
+        }
+
+        let mut planned = Vec::new();
+        let mut skipped = Vec::new();
+        // Inputs declared by each layer, to find stale answers
+        let mut declared = HashMap::new();
+        for layer in &layers {
+            let (module_config, block) = config.find_module(&layer.reference)?;
+
+            let resolved = resolver.resolve(module_config)?;
+            lockfile
+                .modules
+                .insert(module_config.name.clone(), resolved.lock);
+            let module_path = resolved.path;
+            let manifest_path = module_path.join("weaver.module.yaml");
+            let manifest = ModuleManifest::load(&manifest_path)?;
 
-                    // Write File
-                    if dry_run {
-                        // Just log
-                        info!("Would copy {:?} to {:?}", entry.path(), dest_path);
-                    } else {
-                        if !dest_path.exists() {
-                            if let Some(parent) = dest_path.parent() {
-                                std::fs::create_dir_all(parent)?;
-                            }
-                        } else if args.strategy == "stop" && !args.auto_approve {
-                            // Double check: if it exists, it might be identical.
-                            // Or it is unmanaged.
-                            // But if we passed drift check above (managed & matched), we are fine.
-                            // If unmanaged (not in state), we are taking ownership.
-                        }
-
-                        std::fs::copy(entry.path(), &dest_path)?;
-
-                        // Update State
-                        let new_chk = calculate_checksum(&dest_path)?;
-                        state.files.insert(
-                            dest_path.clone(),
-                            FileState {
-                                checksum: new_chk,
-                                last_updated: "now".to_string(),
-                            },
-                        );
+            // Blocks are named fragments of a module, stored under `blocks/<name>`
+            let content_root = match &block {
+                Some(block) => {
+                    let block_path = module_path.join("blocks").join(block);
+                    if !block_path.is_dir() {
+                        anyhow::bail!("Module '{}' has no block '{}'", module_config.name, block);
                     }
+                    block_path
                 }
-            }
-        }
+                None => module_path.clone(),
+            };
 
-        // Templates Processing (Similar logic can be added here, omitting for brevity in this step)
-        // ... (existing template logic adapted to manual write/state update) ...
-        let templates_src = module_path.join("templates");
-        if templates_src.exists() {
-            for entry in walkdir::WalkDir::new(&templates_src) {
-                let entry = entry?;
-                if entry.file_type().is_file() {
-                    let rel_path = entry.path().strip_prefix(&templates_src)?;
-                    let content = std::fs::read_to_string(entry.path())?;
-                    let mut context = tera_context.clone();
-                    for (k, v) in &app.inputs {
-                        // TODO: handle types properly
-                        context.insert(k, v);
-                    }
-                    // Basic rendering, should use template_engine properly
-                    // For MVP just text replacement logic or tera one-off?
-                    // Existing code used manual text/tera context?
-                    // Wait, Step 953 showed "let mut context = tera_context.clone()".
-                    // And it didn't actually render in the placeholder code I saw.
-                    // I will assume simple copy or placeholder for now.
-                    // Actually, I should use template_engine.render if available.
-                    // But let's stick to simple text for now or just copy.
-
-                    // Destination logic
-                    let file_name = entry.file_name().to_string_lossy();
-                    let dest_path = if file_name.ends_with(".j2") {
-                        dest_root
-                            .join(rel_path.parent().unwrap_or(Path::new("")))
-                            .join(rel_path.file_stem().unwrap())
-                    } else {
-                        dest_root.join(rel_path)
-                    };
-
-                    // Drift Check
-                    if dest_path.exists() {
-                        let current_chk = calculate_checksum(&dest_path)?;
-                        if let Some(file_state) = state.files.get(&dest_path) {
-                            if file_state.checksum != current_chk {
-                                if args.strategy == "stop" && !args.auto_approve {
-                                    if dry_run {
-                                        info!(
-                                            "Drift detected for {:?}. Plan would fail.",
-                                            dest_path
-                                        );
-                                    }
-                                    anyhow::bail!(
-                                        "Drift detected for {:?}. Use --strategy overwrite to force.",
-                                        dest_path
-                                    );
-                                } else if dry_run {
-                                    info!(
-                                        "Drift detected for {:?}. Plan would overwrite.",
-                                        dest_path
-                                    );
-                                }
-                            }
-                        }
-                    }
+            let (mut inputs, mut secret_inputs) =
+                interpolate::resolve_inputs(&layer.inputs, secrets)
+                    .map_err(|e| anyhow::anyhow!("App '{}': {}", app_config.name, e))?;
+            let specs = manifest
+                .inputs
+                .iter()
+                .map(|(key, def)| {
+                    InputSpec::of(def)
+                        .map(|spec| (key, spec))
+                        .map_err(|e| anyhow::anyhow!("Input '{}': {}", key, e))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            // Supplied variables override the inputs from weaver.yaml
+            for (key, value) in vars.layer_values(&app_config.name, specs)? {
+                secret_inputs.remove(&key);
+                inputs.insert(key, value);
+            }
 
-                    if dry_run {
-                        info!("Would render {:?} to {:?}", entry.path(), dest_path);
-                    } else {
-                        if let Some(parent) = dest_path.parent() {
-                            std::fs::create_dir_all(parent)?;
-                        }
-                        // Write content (simulated render)
-                        std::fs::write(&dest_path, &content)?; // Todo: Render
-
-                        let new_chk = calculate_checksum_from_bytes(content.as_bytes());
-                        state.files.insert(
-                            dest_path.clone(),
-                            FileState {
-                                checksum: new_chk,
-                                last_updated: "now".to_string(),
-                            },
-                        );
-                    }
+            // Resolve missing inputs (Interactive)
+            let saved =
+                answers.layer_answers(&app_config.name, &layer.reference, &manifest.inputs)?;
+            declared.insert(
+                layer.reference.clone(),
+                manifest.inputs.keys().cloned().collect::<Vec<_>>(),
+            );
+            // Secret-backed inputs are only marked as set: neither their
+            // reference text nor their value may reach templated defaults
+            let secret_keys: HashSet<&str> = secret_inputs.keys().map(String::as_str).collect();
+            // Commands deriving inputs run in the app folder once it exists
+            let app_dir = Path::new(&app_config.path);
+            let app_dir = if app_dir.is_dir() {
+                app_dir
+            } else {
+                Path::new(".")
+            };
+            let resolution = crate::prompts::resolve_missing_inputs(
+                &manifest,
+                &inputs,
+                &secret_keys,
+                &saved,
+                app_dir,
+                interactive,
+            )
+            .map_err(|e| anyhow::anyhow!("App '{}': {}", app_config.name, e))?;
+            answers.record(
+                &app_config.name,
+                &layer.reference,
+                resolution.prompted,
+                AnswerOrigin::Prompt,
+            );
+            answers.record(
+                &app_config.name,
+                &layer.reference,
+                resolution.computed,
+                AnswerOrigin::Computed,
+            );
+            // Saved right away so answers survive a failure later in the run;
+            // plan keeps them in memory only
+            if !dry_run {
+                answers.save(false)?;
+            }
+            if !resolution.missing.is_empty() {
+                missing.extend(resolution.missing.into_iter().map(|key| {
+                    format!(
+                        "app '{}', module '{}': {}",
+                        app_config.name, layer.reference, key
+                    )
+                }));
+                continue;
+            }
+            for (key, value) in resolution.inputs {
+                if !secret_inputs.contains_key(&key) {
+                    inputs.entry(key).or_insert(value);
                 }
             }
+
+            let app = App::instantiate(
+                app_config,
+                &layer.reference,
+                &inputs,
+                secret_inputs,
+                &manifest,
+            )?;
+
+            let context = TemplateContext {
+                app: &app,
+                module: module_config,
+                block: block.as_deref(),
+                module_path: &module_path,
+                workspace_root: &workspace_root,
+                secrets: &resolved_secrets,
+            };
+            let context = Rc::new(context.build()?);
+
+            // Compiled once per module ref, shared by every app using it
+            template_engine.load(
+                &content_root.join("templates"),
+                &format!("{}/templates", layer.reference),
+            )?;
+            let layer_skipped = collect_files(
+                &content_root,
+                &app.path,
+                &layer.reference,
+                &manifest.files,
+                &template_engine,
+                &context,
+                &mut planned,
+            )
+            .map_err(|e| anyhow::anyhow!("App '{}': {}", app_config.name, e))?;
+            skipped.extend(layer_skipped);
+        }
+
+        let declared_keys: Vec<&str> = declared.values().flatten().map(String::as_str).collect();
+        for key in vars.unknown_inputs(&app_config.name, &declared_keys) {
+            warn!(
+                "Variable '{}.{}' matches no input of the app's modules",
+                app_config.name, key
+            );
+        }
+
+        let stale = answers.stale_answers(&app_config.name, &declared);
+        if !stale.is_empty()
+            && prune_stale_answers(&app_config.name, &stale, args, interactive, dry_run)?
+        {
+            answers.remove(&app_config.name, &stale);
+            answers.save(false)?;
+        }
+
+        apps.push((app_config, planned, skipped));
+    }
+
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Missing values for inputs (prompts disabled; use --var, --var-file or RW_VAR_<APP>_<KEY>):\n  - {}",
+            missing.join("\n  - ")
+        );
+    }
+
+    for (app_config, planned, skipped) in apps {
+        for file in resolve_conflicts(app_config, planned)? {
+            apply_file(&file, &template_engine, &mut state, args, dry_run)?;
+        }
+        for file in skipped {
+            info!(
+                "{} {} for app '{}': {}",
+                if dry_run { "Would skip" } else { "Skipped" },
+                file.origin,
+                app_config.name,
+                file.reason
+            );
         }
     }
 
     if !dry_run {
+        let app_names: Vec<&str> = config.apps.iter().map(|a| a.name.as_str()).collect();
+        for removed in answers.retain_apps(&app_names) {
+            info!("Removed saved answers of app '{}'", removed);
+        }
+        answers.save(true)?;
         state.save(state_path)?;
+        if !lockfile.modules.is_empty() || lockfile_path.exists() {
+            lockfile.save(lockfile_path)?;
+        }
         info!("Apply complete.");
     } else {
         info!("Plan complete. No changes made.");
@@ -249,3 +349,319 @@ pub async fn execute(args: ApplyArgs, dry_run: bool) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Reports saved answers of `app` that no module input uses any more (the
+/// module dropped the input, or the app no longer uses the module) and
+/// decides whether to delete them.
+fn prune_stale_answers(
+    app: &str,
+    stale: &[(String, String)],
+    args: &ApplyArgs,
+    interactive: bool,
+    dry_run: bool,
+) -> anyhow::Result<bool> {
+    let listed = stale
+        .iter()
+        .map(|(module, key)| format!("{}.{}", module, key))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let prune = match args.stale_answers {
+        // Plan never deletes anything
+        _ if dry_run => false,
+        StaleAnswers::Prune => true,
+        StaleAnswers::Keep => false,
+        StaleAnswers::Ask if interactive => {
+            dialoguer::Confirm::with_theme(&dialoguer::theme::ColorfulTheme::default())
+                .with_prompt(format!(
+                    "App '{}' has saved answers no module input uses: {}. Delete them?",
+                    app, listed
+                ))
+                .default(false)
+                .interact()?
+        }
+        StaleAnswers::Ask => false,
+    };
+    if prune {
+        info!("Deleted stale answers of app '{}': {}", app, listed);
+    } else {
+        warn!(
+            "App '{}' has saved answers no module input uses: {} (delete them with --stale-answers prune)",
+            app, listed
+        );
+    }
+    Ok(prune)
+}
+
+/// Plans the `files/` and `templates/` of a module (or block) rooted at
+/// `content_root` into the app folder `dest_root`. Returns the files left out
+/// by the manifest's `files` rules or an empty path segment.
+fn collect_files(
+    content_root: &Path,
+    dest_root: &Path,
+    layer: &str,
+    rules: &[FileRule],
+    template_engine: &TemplateEngine,
+    context: &Rc<tera::Context>,
+    planned: &mut Vec<PlannedFile>,
+) -> anyhow::Result<Vec<SkippedFile>> {
+    let mut skipped = Vec::new();
+    // Origin of each destination of this layer, to report collisions
+    let mut origins: HashMap<PathBuf, String> = HashMap::new();
+
+    for (folder, kind) in [("files", FileKind::Copy), ("templates", FileKind::Template)] {
+        let src = content_root.join(folder);
+        if !src.exists() {
+            continue;
+        }
+        for entry in WalkDir::new(&src).sort_by_file_name() {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let rel_path = entry.path().strip_prefix(&src)?;
+            if kind == FileKind::Template && template::is_shared(rel_path) {
+                continue;
+            }
+            let origin = origin(layer, folder, rel_path);
+
+            let Some(mut dest_rel) = render_dest(rel_path, &origin, template_engine, context)?
+            else {
+                skipped.push(SkippedFile {
+                    origin,
+                    reason: "a path segment renders empty".to_string(),
+                });
+                continue;
+            };
+            // Templates lose their `.j2` extension
+            if kind == FileKind::Template
+                && let Some(stem) = dest_rel
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".j2"))
+                    .map(str::to_string)
+            {
+                dest_rel.set_file_name(stem);
+            }
+
+            let mut failed = None;
+            for rule in rules.iter().filter(|rule| rule.matches(&dest_rel)) {
+                if !template_engine.condition(&origin, &rule.when, context)? {
+                    failed = Some(rule);
+                    break;
+                }
+            }
+            if let Some(rule) = failed {
+                skipped.push(SkippedFile {
+                    origin,
+                    reason: format!("'{}' is false (files rule '{}')", rule.when, rule.path),
+                });
+                continue;
+            }
+
+            let dest = dest_root.join(&dest_rel);
+            if let Some(other) = origins.insert(dest.clone(), origin.clone()) {
+                anyhow::bail!(
+                    "'{}' and '{}' both produce '{}'",
+                    other,
+                    origin,
+                    dest_rel.to_string_lossy().replace('\\', "/")
+                );
+            }
+            planned.push(PlannedFile {
+                source: entry.path().to_path_buf(),
+                root: src.clone(),
+                dest,
+                kind,
+                layer: layer.to_string(),
+                origin,
+                context: context.clone(),
+            });
+        }
+    }
+
+    Ok(skipped)
+}
+
+/// Renders the templated segments of a module path (`cmd/{{ name }}/main.go`)
+/// with the app's context. `None` when a segment renders empty, which skips
+/// the file.
+fn render_dest(
+    rel_path: &Path,
+    origin: &str,
+    template_engine: &TemplateEngine,
+    context: &tera::Context,
+) -> anyhow::Result<Option<PathBuf>> {
+    let mut dest = PathBuf::new();
+    for component in rel_path.components() {
+        let segment = component.as_os_str().to_string_lossy();
+        if !segment.contains("{{") && !segment.contains("{%") {
+            dest.push(component);
+            continue;
+        }
+        let rendered = template_engine.render(origin, &segment, context)?;
+        let rendered = rendered.trim();
+        if rendered.is_empty() {
+            return Ok(None);
+        }
+        if rendered == "." || rendered == ".." || rendered.contains(['/', '\\', '\0']) {
+            anyhow::bail!(
+                "{}: path segment '{}' renders to '{}', which is not a file or folder name",
+                origin,
+                segment,
+                rendered
+            );
+        }
+        dest.push(rendered);
+    }
+    Ok(Some(dest))
+}
+
+/// Fails when several modules of an app produce the same destination file,
+/// unless the app's `prefer` map names the module that wins.
+fn resolve_conflicts(
+    app_config: &AppConfig,
+    planned: Vec<PlannedFile>,
+) -> anyhow::Result<Vec<PlannedFile>> {
+    let app_root = Path::new(&app_config.path);
+    let mut layers_by_dest: HashMap<&Path, Vec<&str>> = HashMap::new();
+    for file in &planned {
+        let layers = layers_by_dest.entry(&file.dest).or_default();
+        if !layers.contains(&file.layer.as_str()) {
+            layers.push(&file.layer);
+        }
+    }
+
+    let mut winners: HashMap<PathBuf, String> = HashMap::new();
+    let mut conflicts: Vec<_> = layers_by_dest
+        .into_iter()
+        .filter(|(_, layers)| layers.len() > 1)
+        .collect();
+    conflicts.sort();
+
+    for (dest, layers) in conflicts {
+        let rel = dest
+            .strip_prefix(app_root)
+            .unwrap_or(dest)
+            .to_string_lossy()
+            .replace('\\', "/");
+        match app_config.prefer.get(&rel) {
+            Some(preferred) if layers.contains(&preferred.as_str()) => {
+                winners.insert(dest.to_path_buf(), preferred.clone());
+            }
+            Some(preferred) => anyhow::bail!(
+                "App '{}': 'prefer' picks '{}' for '{}', but only {} produce that file",
+                app_config.name,
+                preferred,
+                rel,
+                quote_list(&layers)
+            ),
+            None => anyhow::bail!(
+                "App '{}': '{}' is produced by {}. Add `prefer: {{ \"{}\": <module> }}` to choose one.",
+                app_config.name,
+                rel,
+                quote_list(&layers),
+                rel
+            ),
+        }
+    }
+
+    Ok(planned
+        .into_iter()
+        .filter(|file| {
+            winners
+                .get(&file.dest)
+                .is_none_or(|winner| *winner == file.layer)
+        })
+        .collect())
+}
+
+fn origin(layer: &str, folder: &str, rel_path: &Path) -> String {
+    format!(
+        "{}/{}/{}",
+        layer,
+        folder,
+        rel_path.to_string_lossy().replace('\\', "/")
+    )
+}
+
+fn quote_list(items: &[&str]) -> String {
+    items
+        .iter()
+        .map(|item| format!("'{}'", item))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn apply_file(
+    file: &PlannedFile,
+    template_engine: &TemplateEngine,
+    state: &mut State,
+    args: &ApplyArgs,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let dest_path = &file.dest;
+
+    // Check Drift
+    if dest_path.exists() {
+        let current_chk = calculate_checksum(dest_path)?;
+        if let Some(file_state) = state.files.get(dest_path)
+            && file_state.checksum != current_chk
+        {
+            // Drift detected
+            if args.strategy == "stop" && !args.auto_approve {
+                if dry_run {
+                    info!("Drift detected for {:?}. Plan would fail.", dest_path);
+                }
+                anyhow::bail!(
+                    "Drift detected for {:?}. Use --strategy overwrite to force.",
+                    dest_path
+                );
+            } else if dry_run {
+                info!("Drift detected for {:?}. Plan would overwrite.", dest_path);
+            }
+        }
+    }
+
+    if dry_run {
+        // Just log
+        match file.kind {
+            FileKind::Copy => info!("Would copy {:?} to {:?}", file.source, dest_path),
+            FileKind::Template => info!("Would render {:?} to {:?}", file.source, dest_path),
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = dest_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let new_chk = match file.kind {
+        FileKind::Copy => {
+            std::fs::copy(&file.source, dest_path)?;
+            calculate_checksum(dest_path)?
+        }
+        FileKind::Template => {
+            let content = template_engine
+                .render_file(
+                    &file.root,
+                    file.source.strip_prefix(&file.root)?,
+                    &file.origin,
+                    &file.context,
+                )
+                .map_err(|e| anyhow::anyhow!("Failed to render {}", e))?;
+            std::fs::write(dest_path, &content)?;
+            calculate_checksum_from_bytes(content.as_bytes())
+        }
+    };
+
+    // Update State
+    state.files.insert(
+        dest_path.clone(),
+        FileState {
+            checksum: new_chk,
+            last_updated: "now".to_string(),
+        },
+    );
+
+    Ok(())
+}
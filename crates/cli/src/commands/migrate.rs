@@ -0,0 +1,136 @@
+use clap::Args;
+use console::style;
+use dialoguer::{Confirm, theme::ColorfulTheme};
+use repo_weaver_core::loader;
+use repo_weaver_core::migrate::{self, DocumentKind};
+use similar::{ChangeTag, TextDiff};
+use std::path::{Path, PathBuf};
+use tracing::info;
+use walkdir::WalkDir;
+
+#[derive(Args)]
+pub struct MigrateArgs {
+    /// Only show the changes, don't write them
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Write the changes without asking for confirmation
+    #[arg(long)]
+    pub yes: bool,
+}
+
+/// A file whose migrated content differs from what is on disk.
+struct PendingRewrite {
+    path: PathBuf,
+    old: String,
+    new: String,
+}
+
+pub fn run(args: MigrateArgs) -> anyhow::Result<()> {
+    let config_path = Path::new("weaver.yaml");
+    if !config_path.exists() {
+        anyhow::bail!("weaver.yaml not found");
+    }
+
+    let mut documents: Vec<(PathBuf, DocumentKind)> = loader::config_files(config_path)?
+        .into_iter()
+        .map(|path| (path, DocumentKind::Config))
+        .collect();
+    documents.extend(
+        find_manifests(Path::new("."))?
+            .into_iter()
+            .map(|path| (path, DocumentKind::Manifest)),
+    );
+    let state_path = PathBuf::from(".rw/state.yaml");
+    if state_path.exists() {
+        documents.push((state_path, DocumentKind::State));
+    }
+
+    let mut pending = Vec::new();
+    for (path, kind) in documents {
+        let old = std::fs::read_to_string(&path)?;
+        let mut doc: serde_yml::Value =
+            serde_yml::from_str(&old).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        if migrate::migrate(kind, &mut doc)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?
+        {
+            let new = serde_yml::to_string(&doc)?;
+            pending.push(PendingRewrite { path, old, new });
+        }
+    }
+
+    if pending.is_empty() {
+        println!("Everything is up to date.");
+        return Ok(());
+    }
+
+    for rewrite in &pending {
+        print_diff(rewrite);
+    }
+
+    if args.dry_run {
+        println!("{} file(s) would be migrated.", pending.len());
+        return Ok(());
+    }
+
+    // Comments and formatting are not preserved, so always ask first
+    if !args.yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Rewrite {} file(s)?", pending.len()))
+            .default(false)
+            .interact()?
+    {
+        println!("Aborted, nothing was written.");
+        return Ok(());
+    }
+
+    for rewrite in &pending {
+        std::fs::write(&rewrite.path, &rewrite.new)?;
+        info!("Migrated {}", rewrite.path.display());
+    }
+    println!("Migrated {} file(s).", pending.len());
+
+    Ok(())
+}
+
+/// Module manifests kept inside the workspace (e.g. modules developed in
+/// the same monorepo). Hidden directories such as `.rw/` are skipped.
+fn find_manifests(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let walker = WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0
+                || !(entry.file_type().is_dir()
+                    && entry.file_name().to_string_lossy().starts_with('.'))
+        });
+
+    let mut manifests = Vec::new();
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.file_name() == "weaver.module.yaml" {
+            manifests.push(entry.into_path());
+        }
+    }
+    Ok(manifests)
+}
+
+fn print_diff(rewrite: &PendingRewrite) {
+    let name = rewrite.path.display().to_string();
+    println!("{}", style(format!("--- {}", name)).bold());
+    println!("{}", style(format!("+++ {}", name)).bold());
+
+    let diff = TextDiff::from_lines(&rewrite.old, &rewrite.new);
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        println!("{}", style(hunk.header()).cyan());
+        for change in hunk.iter_changes() {
+            let line = change.to_string_lossy();
+            let line = line.trim_end_matches('\n');
+            match change.tag() {
+                ChangeTag::Delete => println!("{}", style(format!("-{}", line)).red()),
+                ChangeTag::Insert => println!("{}", style(format!("+{}", line)).green()),
+                ChangeTag::Equal => println!(" {}", line),
+            }
+        }
+    }
+}
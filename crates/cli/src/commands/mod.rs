@@ -1,5 +1,9 @@
+pub mod answers;
 pub mod apply;
 pub mod init;
+pub mod migrate;
 pub mod plan;
 pub mod run;
+pub mod schema;
+pub mod validate;
 // For later
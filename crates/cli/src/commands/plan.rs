@@ -11,6 +11,9 @@ pub struct PlanArgs {
     /// Save the plan to a file
     #[arg(long)]
     pub out: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub vars: crate::vars::VarArgs,
 }
 
 pub async fn run(args: PlanArgs) -> anyhow::Result<()> {
@@ -20,6 +23,9 @@ pub async fn run(args: PlanArgs) -> anyhow::Result<()> {
     let apply_args = crate::commands::apply::ApplyArgs {
         auto_approve: false,          // Plan is interactive for inputs
         strategy: "stop".to_string(), // Default strategy to detect drift
+        // Plan only reports stale answers
+        stale_answers: crate::commands::apply::StaleAnswers::Keep,
+        vars: args.vars.clone(),
     };
 
     let result = crate::commands::apply::execute(apply_args, true).await;
@@ -38,26 +38,22 @@ pub async fn run(args: RunArgs) -> anyhow::Result<()> {
         .find(|a| a.name == args.app_name)
         .ok_or_else(|| anyhow::anyhow!("App '{}' not found", args.app_name))?;
 
-    // Find module
-    let module_config = config
-        .modules
-        .iter()
-        .find(|m| m.name == app_config.module)
-        .ok_or_else(|| anyhow::anyhow!("Module '{}' not found", app_config.module))?;
-
-    // Resolve module
+    // Find task, later modules of the app override tasks of earlier ones
     let resolver = ModuleResolver::new(None)?;
-    let module_path = resolver.resolve(&module_config.source, &module_config.r#ref)?;
-
-    // Load manifest
-    let manifest_path = module_path.join("weaver.module.yaml");
-    let manifest = ModuleManifest::load(&manifest_path)?;
-
-    // Find task
-    let task = manifest
-        .tasks
-        .get(&args.task_name)
-        .ok_or_else(|| anyhow::anyhow!("Task '{}' not found in module", args.task_name))?;
+    let mut task = None;
+    for layer in app_config.layers()? {
+        let (module_config, _) = config.find_module(&layer.reference)?;
+        let module_path = resolver.resolve(module_config)?.path;
+
+        // Load manifest
+        let manifest_path = module_path.join("weaver.module.yaml");
+        let mut manifest = ModuleManifest::load(&manifest_path)?;
+        if let Some(found) = manifest.tasks.remove(&args.task_name) {
+            task = Some(found);
+        }
+    }
+    let task =
+        task.ok_or_else(|| anyhow::anyhow!("Task '{}' not found in module", args.task_name))?;
 
     info!("Executing: {}", task.command);
 
@@ -0,0 +1,26 @@
+use clap::{Args, ValueEnum};
+use repo_weaver_core::config::{config_schema, module_schema};
+
+#[derive(Args)]
+pub struct SchemaArgs {
+    /// File to print the JSON Schema for
+    #[arg(value_enum, default_value = "config")]
+    pub target: SchemaTarget,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SchemaTarget {
+    /// weaver.yaml, its fragments and app files
+    Config,
+    /// weaver.module.yaml
+    Module,
+}
+
+pub fn run(args: SchemaArgs) -> anyhow::Result<()> {
+    let schema = match args.target {
+        SchemaTarget::Config => config_schema(),
+        SchemaTarget::Module => module_schema(),
+    };
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
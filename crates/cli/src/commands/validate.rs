@@ -0,0 +1,72 @@
+use clap::Args;
+use repo_weaver_core::module::ModuleResolver;
+use repo_weaver_core::validate::{Diagnostic, ValidateOptions, validate_workspace};
+use std::path::Path;
+use tracing::warn;
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// Don't report required inputs without a value (they are prompted during apply)
+    #[arg(long)]
+    pub allow_missing_inputs: bool,
+}
+
+pub fn run(args: ValidateArgs, json: bool) -> anyhow::Result<()> {
+    let config_path = Path::new("weaver.yaml");
+    if !config_path.exists() {
+        anyhow::bail!("weaver.yaml not found");
+    }
+
+    let resolver = ModuleResolver::new(None)?;
+    let options = ValidateOptions {
+        require_inputs: !args.allow_missing_inputs,
+    };
+    let diagnostics = validate_workspace(config_path, &resolver, &options);
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+
+    if json {
+        let report = serde_json::json!({
+            "valid": errors == 0,
+            "diagnostics": diagnostics,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+        if errors == 0 {
+            println!("Configuration is valid.");
+        }
+    }
+
+    if errors > 0 {
+        if !json {
+            eprintln!("Validation failed with {} error(s).", errors);
+        }
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Validation run ahead of plan/apply. Warnings are logged, errors are
+/// printed with their location and abort the run.
+pub fn check_before_run(config_path: &Path, resolver: &ModuleResolver) -> anyhow::Result<()> {
+    let options = ValidateOptions {
+        // Missing inputs are prompted for, or reported by the input resolution
+        require_inputs: false,
+    };
+    let diagnostics = validate_workspace(config_path, resolver, &options);
+
+    let (errors, warnings): (Vec<&Diagnostic>, Vec<&Diagnostic>) =
+        diagnostics.iter().partition(|d| d.is_error());
+    for diagnostic in warnings {
+        warn!("{}", diagnostic.to_string().trim_end());
+    }
+    if errors.is_empty() {
+        return Ok(());
+    }
+    for diagnostic in &errors {
+        eprintln!("{}", diagnostic);
+    }
+    anyhow::bail!("Validation failed with {} error(s)", errors.len())
+}
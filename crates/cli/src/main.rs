@@ -1,8 +1,9 @@
 mod commands;
 mod prompts;
+mod vars;
 
 use clap::{CommandFactory, Parser};
-use commands::{apply, init, plan};
+use commands::{answers, apply, init, migrate, plan, schema, validate};
 use repo_weaver_core::{LoggingOptions, setup_tracing_with_options};
 
 #[derive(Parser)]
@@ -35,6 +36,14 @@ enum Commands {
     Plan(plan::PlanArgs),
     Apply(apply::ApplyArgs),
     Run(crate::commands::run::RunArgs),
+    /// Upgrade config, module manifests and .rw/ files to the current schema
+    Migrate(migrate::MigrateArgs),
+    /// Print the JSON Schema of weaver.yaml or weaver.module.yaml
+    Schema(schema::SchemaArgs),
+    /// Check weaver.yaml, its fragments and app inputs for errors
+    Validate(validate::ValidateArgs),
+    /// Inspect and edit saved answers
+    Answers(answers::AnswersArgs),
 }
 
 #[tokio::main]
@@ -68,6 +77,18 @@ async fn main() -> anyhow::Result<()> {
         Some(Commands::Run(args)) => {
             crate::commands::run::run(args).await?;
         }
+        Some(Commands::Migrate(args)) => {
+            migrate::run(args)?;
+        }
+        Some(Commands::Schema(args)) => {
+            schema::run(args)?;
+        }
+        Some(Commands::Validate(args)) => {
+            validate::run(args, cli.json)?;
+        }
+        Some(Commands::Answers(args)) => {
+            answers::run(args, cli.json)?;
+        }
         None => {
             Cli::command().print_help()?;
         }
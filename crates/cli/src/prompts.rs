@@ -1,106 +1,305 @@
-use dialoguer::{Input, theme::ColorfulTheme};
-use repo_weaver_core::config::ModuleManifest;
+use console::style;
+use dialoguer::{Confirm, Input, MultiSelect, Password, Select, theme::ColorfulTheme};
+use repo_weaver_core::config::{InputDef, ModuleManifest};
+use repo_weaver_core::input::{self, InputSpec, InputType};
 use serde_yml::Value;
-use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::fs;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
-pub fn load_answers(path: &Path) -> anyhow::Result<HashMap<String, Value>> {
-    if path.exists() {
-        let content = fs::read_to_string(path)?;
-        // Use BTreeMap for stable ordering if we want, but HashMap is fine for internal use.
-        // Serde yaml returns a Value or a Map? from_str can parse into HashMap.
-        let answers: HashMap<String, Value> = serde_yml::from_str(&content)?;
-        Ok(answers)
-    } else {
-        Ok(HashMap::new())
-    }
-}
-
-pub fn save_answers(path: &Path, answers: &HashMap<String, Value>) -> anyhow::Result<()> {
-    // Merge with existing? Or assumes caller handles merging?
-    // Usually we read all, update, save all.
-    // Let's implement logical update: read existing, merge new, save.
-
-    let mut current = load_answers(path).unwrap_or_default();
-    for (k, v) in answers {
-        current.insert(k.clone(), v.clone());
-    }
-
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    // Sort keys for stability? BTreeMap?
-    // Let's convert to BTreeMap for saving.
-    let sorted: BTreeMap<_, _> = current.into_iter().collect();
-
-    let content = serde_yml::to_string(&sorted)?;
-    fs::write(path, content)?;
-    Ok(())
+/// Values resolved for one app layer.
+pub struct Resolution {
+    pub inputs: HashMap<String, Value>,
+    /// Answers entered at a prompt during this run, to be saved
+    pub prompted: HashMap<String, Value>,
+    /// Defaults computed during this run when not interactive, to be saved
+    pub computed: HashMap<String, Value>,
+    /// Active required inputs left without a value when not interactive
+    pub missing: Vec<String>,
 }
 
+/// Resolves the inputs of `manifest` not in `provided_inputs`. Inputs named
+/// in `secret_inputs` are provided too, but their values never reach
+/// conditions and templated defaults.
 pub fn resolve_missing_inputs(
     manifest: &ModuleManifest,
     provided_inputs: &HashMap<String, Value>,
+    secret_inputs: &HashSet<&str>,
+    saved_answers: &HashMap<String, Value>,
+    app_dir: &Path,
     interactive: bool,
-    answers_file: &Path,
-) -> anyhow::Result<HashMap<String, Value>> {
+) -> anyhow::Result<Resolution> {
     let mut resolved = HashMap::new();
     let theme = ColorfulTheme::default();
+    let mut new_answers = NewAnswers::default();
+    let mut missing = Vec::new();
 
-    // Load previously saved answers
-    let saved_answers = load_answers(answers_file).unwrap_or_default();
-    let mut new_answers = HashMap::new();
+    // Inputs are asked for in declaration order, after the inputs their
+    // `when` condition or templated default refer to
+    let order = input::resolution_order(&manifest.inputs)?;
+    let mut context: HashMap<String, Value> = manifest
+        .inputs
+        .keys()
+        .map(|key| (key.clone(), Value::Null))
+        .collect();
 
-    for (key, def) in &manifest.inputs {
-        // 1. Provided explicitly?
-        if let Some(val) = provided_inputs.get(key) {
-            resolved.insert(key.clone(), val.clone());
+    for key in &order {
+        let def = &manifest.inputs[key];
+        let spec = InputSpec::of(def).map_err(|e| anyhow::anyhow!("Input '{}': {}", key, e))?;
+        if !input::is_active(def, &context)
+            .map_err(|e| anyhow::anyhow!("Input '{}': {}", key, e))?
+        {
             continue;
         }
-
-        // 2. Saved answer?
-        if let Some(val) = saved_answers.get(key) {
-            resolved.insert(key.clone(), val.clone());
+        if secret_inputs.contains(key.as_str()) {
             continue;
         }
 
-        // 3. Default?
-        if let Some(default_val) = &def.default {
-            // If default exists, use it.
-            resolved.insert(key.clone(), default_val.clone());
+        let Some(val) = resolve_input(
+            &theme,
+            key,
+            def,
+            &spec,
+            &Known {
+                provided: provided_inputs,
+                saved: saved_answers,
+                context: &context,
+                app_dir,
+            },
+            interactive,
+            &mut new_answers,
+        )?
+        else {
+            // Optional inputs stay unset. Later inputs still resolve, so
+            // every missing required input is reported
+            if def.required {
+                missing.push(key.clone());
+            }
             continue;
-        }
+        };
+        // Later conditions compare typed values where possible
+        context.insert(
+            key.clone(),
+            spec.check(&val).unwrap_or_else(|_| val.clone()),
+        );
+        resolved.insert(key.clone(), val);
+    }
 
-        // 4. Missing and Required -> Prompt
-        if !interactive {
-            anyhow::bail!(
-                "Missing required input '{}' and interactive mode disabled.",
-                key
-            );
-        }
+    Ok(Resolution {
+        inputs: resolved,
+        prompted: new_answers.prompted,
+        computed: new_answers.computed,
+        missing,
+    })
+}
+
+/// Answers obtained while resolving, by how they were obtained.
+#[derive(Default)]
+struct NewAnswers {
+    prompted: HashMap<String, Value>,
+    computed: HashMap<String, Value>,
+}
+
+/// Values an input can be resolved from besides its default and a prompt.
+struct Known<'a> {
+    provided: &'a HashMap<String, Value>,
+    saved: &'a HashMap<String, Value>,
+    /// Inputs resolved so far, for conditions and templated defaults
+    context: &'a HashMap<String, Value>,
+    /// Where `from_command` runs
+    app_dir: &'a Path,
+}
+
+/// Resolves one active input: explicit value, command output, saved answer, default
+/// (non-interactive) or prompt. New answers are added to `new_answers`.
+/// `None` when not interactive and the input has no value.
+fn resolve_input(
+    theme: &ColorfulTheme,
+    key: &str,
+    def: &InputDef,
+    spec: &InputSpec,
+    known: &Known,
+    interactive: bool,
+    new_answers: &mut NewAnswers,
+) -> anyhow::Result<Option<Value>> {
+    // 1. Provided explicitly?
+    if let Some(val) = known.provided.get(key) {
+        return Ok(Some(val.clone()));
+    }
+
+    // 2. Derived from a command? Never saved, so it is run again each time
+    if let Some(val) = input::derived_value(def, known.app_dir)
+        .map_err(|e| anyhow::anyhow!("Input '{}': {}", key, e))?
+    {
+        return spec
+            .check(&val)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Input '{}' (from command): {}", key, e));
+    }
+
+    // 3. Saved answer?
+    if let Some(val) = known.saved.get(key) {
+        return Ok(Some(val.clone()));
+    }
+
+    let default = input::default_value(def, known.context)
+        .map_err(|e| anyhow::anyhow!("Input '{}': {}", key, e))?;
 
-        let prompt_text = if let Some(desc) = &def.description {
-            format!("{} ({})", key, desc)
-        } else {
-            key.clone()
+    // 4. Non-interactive: default, or report as missing
+    if !interactive {
+        let Some((default_val, computed)) = default else {
+            return Ok(None);
         };
+        // Computed defaults are recorded so later runs see the same value
+        if computed {
+            new_answers
+                .computed
+                .insert(key.to_string(), default_val.clone());
+        }
+        return Ok(Some(default_val));
+    }
+
+    // 5. Prompt, with the default pre-filled
+    let default = default.map(|(v, _)| v);
+    let val = loop {
+        // Text entries are checked as they are typed; selections and
+        // lists can only be checked once complete
+        let answer = prompt_input(theme, key, def, spec, default.as_ref())?;
+        match spec.check(&answer) {
+            Ok(val) => break val,
+            Err(e) => eprintln!("{}", style(format!("{}: {}", key, e)).red()),
+        }
+    };
+    // Sensitive answers are encrypted by the answer store
+    new_answers.prompted.insert(key.to_string(), val.clone());
+    Ok(Some(val))
+}
 
-        let input_str: String = Input::with_theme(&theme)
-            .with_prompt(&prompt_text)
-            .interact_text()?;
+/// Asks for one input with a prompt suited to its type.
+fn prompt_input(
+    theme: &ColorfulTheme,
+    key: &str,
+    def: &InputDef,
+    spec: &InputSpec,
+    default: Option<&Value>,
+) -> anyhow::Result<Value> {
+    let ty = &spec.ty;
+    let label = match &def.description {
+        Some(desc) => format!("{} ({})", key, desc),
+        None => key.to_string(),
+    };
 
-        let val = Value::String(input_str);
-        resolved.insert(key.clone(), val.clone());
-        new_answers.insert(key.clone(), val);
+    if def.sensitive {
+        let text = Password::with_theme(theme)
+            .with_prompt(format!("{} [{}]", label, ty))
+            .allow_empty_password(default.is_some())
+            .validate_with(|text: &String| -> Result<(), String> {
+                if text.is_empty() {
+                    return Ok(());
+                }
+                spec.parse_text(text).map(|_| ()).map_err(|e| e.to_string())
+            })
+            .interact()?;
+        return match default {
+            Some(default) if text.is_empty() => Ok(default.clone()),
+            _ => spec.parse_text(&text),
+        };
     }
 
-    // Save newly collected answers
-    if !new_answers.is_empty() {
-        save_answers(answers_file, &new_answers)?;
+    match ty {
+        InputType::Bool => {
+            let default = default.and_then(Value::as_bool).unwrap_or(false);
+            let answer = Confirm::with_theme(theme)
+                .with_prompt(label)
+                .default(default)
+                .interact()?;
+            Ok(Value::Bool(answer))
+        }
+        InputType::Enum(values) => {
+            let selected = default
+                .map(default_text)
+                .and_then(|d| values.iter().position(|v| *v == d))
+                .unwrap_or(0);
+            let idx = Select::with_theme(theme)
+                .with_prompt(label)
+                .items(values)
+                .default(selected)
+                .interact()?;
+            Ok(Value::String(values[idx].clone()))
+        }
+        InputType::List(Some(item)) if matches!(**item, InputType::Enum(_)) => {
+            let InputType::Enum(values) = &**item else {
+                unreachable!()
+            };
+            let defaults: Vec<String> = default
+                .and_then(Value::as_sequence)
+                .map(|items| items.iter().map(default_text).collect())
+                .unwrap_or_default();
+            let checked: Vec<bool> = values.iter().map(|v| defaults.contains(v)).collect();
+            let picked = MultiSelect::with_theme(theme)
+                .with_prompt(format!("{} (space to toggle)", label))
+                .items(values)
+                .defaults(&checked)
+                .interact()?;
+            Ok(Value::Sequence(
+                picked
+                    .into_iter()
+                    .map(|idx| Value::String(values[idx].clone()))
+                    .collect(),
+            ))
+        }
+        InputType::List(item) => {
+            // One item per prompt, an empty entry ends the list
+            let defaults: Vec<String> = default
+                .and_then(Value::as_sequence)
+                .map(|items| items.iter().map(default_text).collect())
+                .unwrap_or_default();
+            let mut items = Vec::new();
+            loop {
+                let text: String = Input::with_theme(theme)
+                    .with_prompt(format!(
+                        "{} [item {}, empty to finish]",
+                        label,
+                        items.len() + 1
+                    ))
+                    .with_initial_text(defaults.get(items.len()).cloned().unwrap_or_default())
+                    .allow_empty(true)
+                    .validate_with(|text: &String| -> Result<(), String> {
+                        match item {
+                            Some(item) if !text.is_empty() => {
+                                item.parse_text(text).map(|_| ()).map_err(|e| e.to_string())
+                            }
+                            _ => Ok(()),
+                        }
+                    })
+                    .interact_text()?;
+                if text.is_empty() {
+                    break;
+                }
+                items.push(match item {
+                    Some(item) => item.parse_text(&text)?,
+                    None => Value::String(text),
+                });
+            }
+            Ok(Value::Sequence(items))
+        }
+        _ => {
+            let text: String = Input::with_theme(theme)
+                .with_prompt(format!("{} [{}]", label, ty))
+                .with_initial_text(default.map(default_text).unwrap_or_default())
+                .validate_with(|text: &String| -> Result<(), String> {
+                    spec.parse_text(text).map(|_| ()).map_err(|e| e.to_string())
+                })
+                .interact_text()?;
+            spec.parse_text(&text)
+        }
     }
+}
 
-    Ok(resolved)
+/// Text shown for a default value; structured values use JSON, which is
+/// also valid YAML flow syntax.
+fn default_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
 }
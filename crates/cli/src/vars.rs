@@ -0,0 +1,165 @@
+//! Input values supplied on the command line or through the environment,
+//! for runs that must not edit `weaver.yaml` or wait for prompts.
+//!
+//! Values of an app's inputs are taken from, highest precedence first:
+//! 1. `--var app.key=value` (the last one wins when repeated)
+//! 2. `--var-file vars.yaml` (later files win), mapping app -> input -> value
+//! 3. `RW_VAR_<APP>_<KEY>` environment variables
+//! 4. `inputs` in `weaver.yaml` (module-specific before app-wide)
+//! 5. The output of the input's `from_command`
+//! 6. Saved answers
+//! 7. Prompts, with the default pre-filled, or the default when not interactive
+
+use clap::Args;
+use repo_weaver_core::config::WeaverConfig;
+use repo_weaver_core::input::InputSpec;
+use serde_yml::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct VarArgs {
+    /// Set an app input, e.g. `--var api.replicas=3` (repeatable)
+    #[arg(long = "var", value_name = "APP.KEY=VALUE")]
+    pub vars: Vec<String>,
+
+    /// YAML file of app inputs (`app: {key: value}`, repeatable)
+    #[arg(long = "var-file", value_name = "FILE")]
+    pub var_files: Vec<PathBuf>,
+
+    /// Never prompt: fail listing every input without a value
+    #[arg(long)]
+    pub no_input: bool,
+}
+
+/// A supplied value: text from the command line or environment is parsed
+/// according to the input's type, values from var files are used as is.
+#[derive(Debug, Clone, PartialEq)]
+enum VarValue {
+    Text(String),
+    Value(Value),
+}
+
+/// Input values supplied for each app, see the module docs for precedence.
+pub struct Vars {
+    /// app -> input -> value from `--var` and `--var-file`
+    apps: HashMap<String, HashMap<String, VarValue>>,
+    env: HashMap<String, String>,
+}
+
+impl Vars {
+    pub fn load(args: &VarArgs, config: &WeaverConfig) -> anyhow::Result<Self> {
+        Self::with_env(args, config, std::env::vars().collect())
+    }
+
+    fn with_env(
+        args: &VarArgs,
+        config: &WeaverConfig,
+        env: HashMap<String, String>,
+    ) -> anyhow::Result<Self> {
+        let mut apps: HashMap<String, HashMap<String, VarValue>> = HashMap::new();
+        let known_app = |app: &str, origin: &str| -> anyhow::Result<()> {
+            if !config.apps.iter().any(|a| a.name == app) {
+                anyhow::bail!("{}: app '{}' not found in weaver.yaml", origin, app);
+            }
+            Ok(())
+        };
+
+        for path in &args.var_files {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+            let file: BTreeMap<String, BTreeMap<String, Value>> = serde_yml::from_str(&content)
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "{}: expected app names mapped to their inputs: {}",
+                        path.display(),
+                        e
+                    )
+                })?;
+            for (app, inputs) in file {
+                known_app(&app, &path.display().to_string())?;
+                let entry = apps.entry(app).or_default();
+                for (key, value) in inputs {
+                    entry.insert(key, VarValue::Value(value));
+                }
+            }
+        }
+
+        for var in &args.vars {
+            let (target, value) = var
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("--var {}: expected APP.KEY=VALUE", var))?;
+            let (app, key) = target
+                .split_once('.')
+                .filter(|(app, key)| !app.is_empty() && !key.is_empty())
+                .ok_or_else(|| anyhow::anyhow!("--var {}: expected APP.KEY=VALUE", var))?;
+            known_app(app, &format!("--var {}", var))?;
+            apps.entry(app.to_string())
+                .or_default()
+                .insert(key.to_string(), VarValue::Text(value.to_string()));
+        }
+
+        Ok(Self { apps, env })
+    }
+
+    /// Values supplied for the inputs of one layer of `app`, keyed by input
+    /// name and converted to the types declared by `specs`.
+    pub fn layer_values<'a>(
+        &self,
+        app: &str,
+        specs: impl IntoIterator<Item = (&'a String, InputSpec)>,
+    ) -> anyhow::Result<HashMap<String, Value>> {
+        let supplied = self.apps.get(app);
+        let mut values = HashMap::new();
+        for (key, spec) in specs {
+            let var = supplied
+                .and_then(|inputs| inputs.get(key))
+                .cloned()
+                .or_else(|| {
+                    self.env
+                        .get(&env_name(app, key))
+                        .map(|text| VarValue::Text(text.clone()))
+                });
+            let value = match var {
+                Some(VarValue::Text(text)) => spec.parse_text(&text),
+                Some(VarValue::Value(value)) => spec.check(&value),
+                None => continue,
+            }
+            .map_err(|e| anyhow::anyhow!("App '{}': variable '{}': {}", app, key, e))?;
+            values.insert(key.clone(), value);
+        }
+        Ok(values)
+    }
+
+    /// Inputs supplied for `app` through `--var` or `--var-file` that none
+    /// of `declared` matches.
+    pub fn unknown_inputs(&self, app: &str, declared: &[&str]) -> Vec<String> {
+        let mut unknown: Vec<String> = self
+            .apps
+            .get(app)
+            .into_iter()
+            .flat_map(|inputs| inputs.keys())
+            .filter(|key| !declared.contains(&key.as_str()))
+            .cloned()
+            .collect();
+        unknown.sort();
+        unknown
+    }
+}
+
+/// `RW_VAR_<APP>_<KEY>`, upper-cased, with characters other than letters
+/// and digits replaced by `_`.
+pub fn env_name(app: &str, key: &str) -> String {
+    let normalize = |s: &str| {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>()
+    };
+    format!("RW_VAR_{}_{}", normalize(app), normalize(key))
+}
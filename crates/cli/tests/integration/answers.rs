@@ -0,0 +1,109 @@
+use crate::common::{TestContext, cmd};
+use predicates::prelude::*;
+
+fn setup() -> TestContext {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        r#"
+inputs:
+  name: { type: string }
+  replicas: { type: int, validation: [{ min: 1 }] }
+  region: { type: string, default: eu-west-1 }
+  owner: { type: string }
+  token: { type: string, sensitive: true }
+"#,
+    );
+    ctx.write_file(
+        "weaver.yaml",
+        r#"
+version: "1"
+modules:
+  - { name: svc, source: ./modules/svc }
+apps:
+  - { name: api, module: svc, path: api, inputs: { name: api } }
+"#,
+    );
+    ctx.write_file(".rw/answers/api.yaml", "svc:\n  replicas: 2\n");
+    ctx
+}
+
+fn answers(ctx: &TestContext, args: &[&str]) -> assert_cmd::assert::Assert {
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .arg("answers")
+        .args(args)
+        .assert()
+}
+
+#[test]
+fn test_list_shows_provenance() {
+    let ctx = setup();
+    answers(&ctx, &["set", "api", "token", "s3cr3t"]).success();
+
+    let output = answers(&ctx, &["list"])
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output).unwrap();
+    let line = |input: &str| {
+        output
+            .lines()
+            .find(|l| l.split_whitespace().nth(2) == Some(input))
+            .unwrap_or_else(|| panic!("no line for {input} in:\n{output}"))
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    assert_eq!(line("name"), "api svc name api explicit config");
+    assert_eq!(line("replicas"), "api svc replicas 2 saved answer");
+    assert_eq!(line("region"), "api svc region eu-west-1 manifest default");
+    assert_eq!(line("owner"), "api svc owner unset (asked on apply)");
+    assert_eq!(line("token"), "api svc token *** set with rw answers");
+}
+
+#[test]
+fn test_set_get_unset_and_export() {
+    let ctx = setup();
+
+    answers(&ctx, &["set", "api", "replicas", "0"])
+        .failure()
+        .stderr(predicate::str::contains("must be at least 1"));
+    answers(&ctx, &["set", "api", "replicas", "three"])
+        .failure()
+        .stderr(predicate::str::contains("expected int"));
+    answers(&ctx, &["set", "api", "missing", "x"])
+        .failure()
+        .stderr(predicate::str::contains("App 'api' has no input 'missing'"));
+
+    answers(&ctx, &["set", "api", "replicas", "5"]).success();
+    answers(&ctx, &["get", "api", "replicas"])
+        .success()
+        .stdout("5\n");
+
+    answers(&ctx, &["set", "api", "owner", "platform"]).success();
+    answers(&ctx, &["set", "api", "name", "billing"])
+        .success()
+        .stdout(predicate::str::contains(
+            "weaver.yaml sets 'name' for app 'api', which takes precedence",
+        ));
+
+    answers(&ctx, &["set", "api", "token", "s3cr3t"]).success();
+    answers(&ctx, &["get", "api", "token"])
+        .success()
+        .stdout("***\n");
+    answers(&ctx, &["get", "api", "token", "--include-sensitive"])
+        .success()
+        .stdout("s3cr3t\n");
+    answers(&ctx, &["unset", "api", "replicas"]).success();
+    answers(&ctx, &["get", "api", "replicas"])
+        .failure()
+        .stderr(predicate::str::contains("no saved answer"));
+
+    answers(&ctx, &["export", "--format", "json"])
+        .success()
+        .stdout(predicate::str::contains("\"owner\": \"platform\""))
+        .stdout(predicate::str::contains("replicas").not());
+}
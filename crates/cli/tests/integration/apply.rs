@@ -1,4 +1,5 @@
-use crate::common::cmd;
+use crate::common::{TestContext, cmd};
+use predicates::prelude::*;
 
 #[test]
 fn test_bootstrap_empty_workspace() {
@@ -10,3 +11,872 @@ fn test_bootstrap_empty_workspace() {
     // Expect success for empty workspace
     assert.success();
 }
+
+#[test]
+fn test_discovered_app_is_applied() {
+    let ctx = TestContext::new();
+    ctx.setup_module("my-mod", "v1", "discovered content");
+
+    let remote_url = format!("file://{}", ctx.root.join("remotes/my-mod").display());
+    ctx.write_file(
+        "weaver.yaml",
+        &format!(
+            r#"
+version: "1"
+discovery:
+  enabled: true
+  exclude: ["remotes/**", "sources/**"]
+modules:
+  - name: "my-mod"
+    source: "{remote_url}"
+    ref: "v1"
+"#
+        ),
+    );
+    ctx.write_file(
+        "services/billing/weaver.app.yaml",
+        "name: billing\nmodule: my-mod\n",
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .arg("apply")
+        .assert()
+        .success();
+
+    assert_eq!(
+        ctx.read_file("services/billing/file.txt"),
+        "discovered content"
+    );
+}
+
+#[test]
+fn test_extends_conflict_requires_prefer() {
+    let ctx = TestContext::new();
+    ctx.setup_module("mod-a", "v1", "from a");
+    ctx.setup_module("mod-b", "v1", "from b");
+
+    let config = |prefer: &str| {
+        let remote =
+            |name: &str| format!("file://{}", ctx.root.join("remotes").join(name).display());
+        format!(
+            r#"
+version: "1"
+modules:
+  - {{ name: "mod-a", source: "{}", ref: "v1" }}
+  - {{ name: "mod-b", source: "{}", ref: "v1" }}
+apps:
+  - name: "app"
+    path: "app"
+    extends: [mod-a, mod-b]
+{prefer}
+"#,
+            remote("mod-a"),
+            remote("mod-b"),
+        )
+    };
+
+    ctx.write_file("weaver.yaml", &config(""));
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .arg("apply")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "'file.txt' is produced by 'mod-a', 'mod-b'",
+        ));
+
+    ctx.write_file("weaver.yaml", &config("    prefer: { file.txt: mod-b }"));
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .arg("apply")
+        .assert()
+        .success();
+
+    assert_eq!(ctx.read_file("app/file.txt"), "from b");
+}
+
+#[test]
+fn test_blocks_are_read_from_their_folder_and_listed_once() {
+    let ctx = TestContext::new();
+    ctx.write_file("modules/svc/weaver.module.yaml", "inputs: {}\n");
+    ctx.write_file("modules/svc/files/main.txt", "main");
+    ctx.write_file("modules/svc/blocks/ci/files/ci.yml", "ci");
+    let config = |extends: &str| {
+        format!(
+            r#"
+version: "1"
+modules:
+  - {{ name: svc, source: ./modules/svc }}
+apps:
+  - {{ name: app, module: svc, path: app, extends: {extends} }}
+"#
+        )
+    };
+
+    ctx.write_file("weaver.yaml", &config("[svc.ci]"));
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--auto-approve"])
+        .assert()
+        .success();
+    assert_eq!(ctx.read_file("app/main.txt"), "main");
+    assert_eq!(ctx.read_file("app/ci.yml"), "ci");
+
+    ctx.write_file("weaver.yaml", &config("[svc.ci, svc.ci]"));
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--auto-approve"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "App 'app' lists 'svc.ci' more than once",
+        ));
+}
+
+#[test]
+fn test_local_module_is_read_in_place() {
+    let ctx = TestContext::new();
+    ctx.write_file("modules/local/weaver.module.yaml", "inputs: {}\n");
+    ctx.write_file("modules/local/files/file.txt", "first");
+    ctx.write_file(
+        "weaver.yaml",
+        r#"
+version: "1"
+modules:
+  - name: "local"
+    source: "./modules/local"
+apps:
+  - name: "app"
+    module: "local"
+    path: "app"
+"#,
+    );
+
+    let apply = || {
+        cmd()
+            .current_dir(&ctx.root)
+            .env("HOME", ctx.root.as_os_str())
+            .args(["apply", "--auto-approve"])
+            .assert()
+            .success();
+    };
+
+    apply();
+    assert_eq!(ctx.read_file("app/file.txt"), "first");
+    let first_lock = ctx.read_file("weaver.lock");
+    assert!(first_lock.contains("checksum: sha256:"), "{first_lock}");
+    assert!(!ctx.root.join(".rw/store").exists());
+
+    // Edits are picked up without committing or bumping a ref
+    ctx.write_file("modules/local/files/file.txt", "second");
+    apply();
+    assert_eq!(ctx.read_file("app/file.txt"), "second");
+    assert_ne!(ctx.read_file("weaver.lock"), first_lock);
+}
+
+#[test]
+fn test_modules_share_one_clone_of_their_repo() {
+    let ctx = TestContext::new();
+    ctx.setup_repo(
+        "catalog",
+        "v1",
+        &[
+            ("network/weaver.module.yaml", "inputs: {}"),
+            ("network/files/network.txt", "network"),
+            ("dns/weaver.module.yaml", "inputs: {}"),
+            ("dns/files/dns.txt", "dns"),
+        ],
+    );
+    let remote_url = format!("file://{}", ctx.root.join("remotes/catalog").display());
+    ctx.write_file(
+        "weaver.yaml",
+        &format!(
+            r#"
+version: "1"
+modules:
+  - {{ name: "network", source: "{remote_url}", ref: "v1", path: "network" }}
+  - {{ name: "dns", source: "{remote_url}", ref: "v1", path: "dns" }}
+apps:
+  - name: "edge"
+    path: "edge"
+    extends: [network, dns]
+"#
+        ),
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--auto-approve"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Cloning").count(1));
+
+    assert_eq!(ctx.read_file("edge/network.txt"), "network");
+    assert_eq!(ctx.read_file("edge/dns.txt"), "dns");
+    let lock = ctx.read_file("weaver.lock");
+    assert!(lock.contains("path: dns"), "{lock}");
+}
+
+#[test]
+fn test_validation_rules_reject_inputs_before_rendering() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/bucket/weaver.module.yaml",
+        r#"
+inputs:
+  bucket:
+    type: string
+    validation:
+      - pattern: "^[a-z0-9-]{3,63}$"
+        message: "bucket names are 3-63 lowercase letters, digits or dashes"
+"#,
+    );
+    ctx.write_file("modules/bucket/files/main.tf", "resource {}");
+    ctx.write_file(
+        "weaver.yaml",
+        r#"
+version: "1"
+modules:
+  - { name: bucket, source: ./modules/bucket }
+apps:
+  - name: state
+    module: bucket
+    path: state
+    inputs: { bucket: "My_Bucket" }
+"#,
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--auto-approve"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "App 'state': input 'bucket' of module 'bucket': bucket names are 3-63 lowercase letters, digits or dashes",
+        ));
+    assert!(!ctx.root.join("state/main.tf").exists());
+}
+
+#[test]
+fn test_conditional_inputs_and_computed_defaults() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/backend/weaver.module.yaml",
+        r#"
+inputs:
+  bucket:
+    type: string
+    default: "{{ name }}-state-bucket"
+    when: "cloud == 'aws'"
+  project:
+    type: string
+    required: true
+    when: "cloud == 'gcp'"
+  cloud: { type: enum, values: [aws, gcp] }
+  name: { type: string }
+"#,
+    );
+    ctx.write_file("modules/backend/files/backend.tf", "terraform {}");
+    ctx.write_file(
+        "weaver.yaml",
+        r#"
+version: "1"
+modules:
+  - { name: backend, source: ./modules/backend }
+apps:
+  - name: billing
+    module: backend
+    path: billing
+    inputs: { cloud: aws, name: billing }
+"#,
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--auto-approve"])
+        .assert()
+        .success();
+
+    // `project` only applies to gcp, the computed bucket name is recorded
+    let answers = ctx.read_file(".rw/answers/billing.yaml");
+    assert_eq!(
+        answers,
+        "backend:\n  bucket: billing-state-bucket\n_origins:\n  backend:\n    bucket: computed\n"
+    );
+}
+
+#[test]
+fn test_answers_are_stored_per_app() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        "inputs:\n  name: { type: string, default: \"{{ 'svc' }}\" }\n",
+    );
+    ctx.write_file(
+        "weaver.yaml",
+        r#"
+version: "1"
+modules:
+  - { name: svc, source: ./modules/svc }
+apps:
+  - { name: api, module: svc, path: api }
+"#,
+    );
+    // Answers from before namespacing
+    ctx.write_file(".rw/answers.yaml", "name: legacy\n");
+    ctx.write_file(".rw/answers/removed.yaml", "svc:\n  name: old\n");
+
+    // Plan adopts the legacy answer in memory only
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .arg("plan")
+        .assert()
+        .success();
+    assert!(!ctx.root.join(".rw/answers/api.yaml").exists());
+    assert!(ctx.root.join(".rw/answers.yaml").exists());
+    assert!(ctx.root.join(".rw/answers/removed.yaml").exists());
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--auto-approve"])
+        .assert()
+        .success();
+
+    assert_eq!(
+        ctx.read_file(".rw/answers/api.yaml"),
+        "svc:\n  name: legacy\n"
+    );
+    assert!(!ctx.root.join(".rw/answers.yaml").exists());
+    assert!(!ctx.root.join(".rw/answers/removed.yaml").exists());
+}
+
+#[test]
+fn test_renamed_inputs_keep_answers_and_stale_ones_are_pruned() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        "inputs:\n  service_name: { type: string, renamed_from: [name] }\n",
+    );
+    ctx.write_file(
+        "weaver.yaml",
+        r#"
+version: "1"
+modules:
+  - { name: svc, source: ./modules/svc }
+apps:
+  - { name: api, module: svc, path: api }
+"#,
+    );
+    ctx.write_file(".rw/answers/api.yaml", "svc:\n  name: api\n  debug: true\n");
+
+    // Not interactive: the stale answer is kept with a warning
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--auto-approve"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("svc.debug"));
+    assert_eq!(
+        ctx.read_file(".rw/answers/api.yaml"),
+        "svc:\n  debug: true\n  service_name: api\n"
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--auto-approve", "--stale-answers", "prune"])
+        .assert()
+        .success();
+    assert_eq!(
+        ctx.read_file(".rw/answers/api.yaml"),
+        "svc:\n  service_name: api\n"
+    );
+}
+
+const VARS_MODULE: &str = r#"
+inputs:
+  name: { type: string, required: true }
+  replicas: { type: int, required: true }
+  region: { type: string, required: true }
+  label: { type: string, default: "{{ name }}-{{ region }}-{{ replicas }}" }
+"#;
+
+const VARS_CONFIG: &str = r#"
+version: "1"
+modules:
+  - { name: svc, source: ./modules/svc }
+apps:
+  - { name: api, module: svc, path: api }
+"#;
+
+#[test]
+fn test_no_input_lists_every_missing_input() {
+    let ctx = TestContext::new();
+    ctx.write_file("modules/svc/weaver.module.yaml", VARS_MODULE);
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["plan", "--no-input", "--var", "api.replicas=2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("app 'api', module 'svc': name"))
+        .stderr(predicate::str::contains("app 'api', module 'svc': region"))
+        .stderr(predicate::str::contains("module 'svc': replicas").not());
+}
+
+#[test]
+fn test_no_input_leaves_optional_inputs_unset() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        r#"
+inputs:
+  name: { type: string, required: true }
+  owner: { type: string, required: false }
+"#,
+    );
+    ctx.write_file(
+        "modules/svc/templates/owner.txt.j2",
+        "{{ name }}: {{ owner | default(value='nobody') }}\n",
+    );
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=api"])
+        .assert()
+        .success();
+    assert_eq!(ctx.read_file("api/owner.txt"), "api: nobody\n");
+}
+
+#[test]
+fn test_vars_from_flags_files_and_environment() {
+    let ctx = TestContext::new();
+    ctx.write_file("modules/svc/weaver.module.yaml", VARS_MODULE);
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+    ctx.write_file("vars.yaml", "api:\n  name: from-file\n  replicas: 2\n");
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .env("RW_VAR_API_REGION", "eu")
+        .env("RW_VAR_API_NAME", "from-env")
+        .args([
+            "apply",
+            "--no-input",
+            "--var-file",
+            "vars.yaml",
+            "--var",
+            "api.name=cli",
+        ])
+        .assert()
+        .success();
+
+    // The computed default shows the value each input was given
+    assert_eq!(
+        ctx.read_file(".rw/answers/api.yaml"),
+        "svc:\n  label: cli-eu-2\n_origins:\n  svc:\n    label: computed\n"
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "web.name=x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("app 'web' not found"));
+}
+
+#[test]
+fn test_inputs_derived_from_commands() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        r#"
+inputs:
+  owner: { type: string, from_command: [cat, OWNER], regex: "owner=(\\w+)" }
+  label: { type: string, default: "{{ owner }}-svc" }
+"#,
+    );
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+    // Run in the app folder
+    ctx.write_file("api/OWNER", "owner=platform\n");
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input"])
+        .assert()
+        .success();
+    // The derived value feeds the computed default but is not saved itself
+    assert_eq!(
+        ctx.read_file(".rw/answers/api.yaml"),
+        "svc:\n  label: platform-svc\n_origins:\n  svc:\n    label: computed\n"
+    );
+
+    std::fs::remove_file(ctx.root.join("api/OWNER")).unwrap();
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "App 'api': Input 'owner': command `cat OWNER` failed",
+        ))
+        .stderr(predicate::str::contains("No such file or directory"));
+}
+
+#[test]
+fn test_secrets_resolve_but_are_never_persisted() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        r#"
+inputs:
+  password: { type: string }
+  label: { type: string, default: "svc{{ password }}" }
+"#,
+    );
+    ctx.write_file(
+        "modules/svc/templates/app.env.j2",
+        "TOKEN={{ secrets.api_token }}\nPASSWORD={{ password }}\n",
+    );
+    ctx.write_file(
+        "weaver.yaml",
+        r#"
+version: "1"
+modules:
+  - { name: svc, source: ./modules/svc }
+apps:
+  - name: api
+    module: svc
+    path: api
+    inputs: { password: "${secret:api_token}" }
+secrets:
+  api_token: { provider: env, key: RW_IT_API_TOKEN }
+"#,
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .env("RW_IT_API_TOKEN", "tok-123")
+        .args(["apply", "--no-input"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("tok-123").not());
+    assert_eq!(
+        ctx.read_file("api/app.env"),
+        "TOKEN=tok-123\nPASSWORD=tok-123\n"
+    );
+    assert!(!ctx.read_file(".rw/state.yaml").contains("tok-123"));
+    // Templated defaults see neither the secret nor its reference
+    let answers = ctx.read_file(".rw/answers/api.yaml");
+    assert!(answers.contains("label: svc\n"), "{answers}");
+
+    // Secret values never show up in errors
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        "inputs:\n  password: { type: int }\n",
+    );
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .env("RW_IT_API_TOKEN", "tok-123")
+        .args(["plan", "--no-input"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("input 'password'"))
+        .stderr(predicate::str::contains("tok-123").not());
+
+    // An unresolved secret fails instead of using a placeholder
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .env_remove("RW_IT_API_TOKEN")
+        .args(["plan", "--no-input"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "secret 'api_token': environment variable 'RW_IT_API_TOKEN' is not set",
+        ));
+}
+
+#[test]
+fn test_sensitive_answers_are_encrypted_at_rest() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        r#"
+inputs:
+  name: { type: string }
+  token: { type: string, sensitive: true, default: "{{ name }}-t0k3n" }
+"#,
+    );
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=api"])
+        .assert()
+        .success();
+    let answers = ctx.read_file(".rw/answers/api.yaml");
+    assert!(answers.contains("token: enc:v1:"), "{answers}");
+    assert!(!answers.contains("t0k3n"), "{answers}");
+    assert!(ctx.root.join(".rw/answers.key").exists());
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .env("RW_ANSWERS_KEY_FILE", ctx.root.join("other.key"))
+        .args(["plan", "--no-input", "--var", "api.name=api"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no key was found"));
+}
+
+#[test]
+fn test_templates_see_app_module_and_workspace() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        "inputs:\n  name: { type: string }\n",
+    );
+    ctx.write_file(
+        "modules/svc/templates/info.txt.j2",
+        "{{ app.name }} {{ app.path }} {{ inputs.name }}\n\
+         {{ module.name }} {{ module.source }} {{ module.ref | default(value='none') }}\n\
+         {{ workspace_root }}\n\
+         {{ rw_version }}\n",
+    );
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=backend"])
+        .assert()
+        .success();
+    let root = ctx.root.canonicalize().unwrap();
+    assert_eq!(
+        ctx.read_file("api/info.txt"),
+        format!(
+            "api api backend\nsvc ./modules/svc none\n{}\n{}\n",
+            root.display(),
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+
+    // Render errors name the template and the line at fault
+    ctx.write_file(
+        "modules/svc/templates/info.txt.j2",
+        "name={{ name }}\nbucket={{ bucket }}\n",
+    );
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=backend"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("svc/templates/info.txt.j2:2: "));
+
+    // Inputs cannot shadow the context
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        "inputs:\n  app: { type: string }\n",
+    );
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["plan", "--no-input"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("reserved by the template context"));
+}
+
+#[test]
+fn test_partials_and_macros_are_shared_but_not_emitted() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        "inputs:\n  name: { type: string }\n",
+    );
+    ctx.write_file(
+        "modules/svc/templates/_partials/header.j2",
+        "# {{ app.name }}\n",
+    );
+    ctx.write_file(
+        "modules/svc/templates/_macros/env.j2",
+        "{% macro var(key, value) %}{{ key | upper }}={{ value }}{% endmacro var %}",
+    );
+    ctx.write_file(
+        "modules/svc/templates/app.env.j2",
+        "{% import \"_macros/env.j2\" as env %}{% include \"_partials/header.j2\" %}{{ env::var(key=\"name\", value=name) }}\n",
+    );
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=backend"])
+        .assert()
+        .success();
+    assert_eq!(ctx.read_file("api/app.env"), "# api\nNAME=backend\n");
+    assert!(!ctx.root.join("api/_partials").exists());
+    assert!(!ctx.root.join("api/_macros").exists());
+}
+
+#[test]
+fn test_destination_paths_are_rendered() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        r#"
+inputs:
+  name: { type: string }
+  env: { type: string, default: "" }
+"#,
+    );
+    ctx.write_file(
+        "modules/svc/templates/cmd/{{ name }}/main.go.j2",
+        "package {{ name }}\n",
+    );
+    ctx.write_file(
+        "modules/svc/files/envs/{{ env }}/main.tfvars",
+        "region = \"eu\"\n",
+    );
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+
+    // An empty segment skips the file
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=backend"])
+        .assert()
+        .success();
+    assert_eq!(
+        ctx.read_file("api/cmd/backend/main.go"),
+        "package backend\n"
+    );
+    assert!(!ctx.root.join("api/envs").exists());
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=backend"])
+        .args(["--var", "api.env=prod"])
+        .assert()
+        .success();
+    assert_eq!(
+        ctx.read_file("api/envs/prod/main.tfvars"),
+        "region = \"eu\"\n"
+    );
+
+    // Segments must stay single names
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["plan", "--no-input", "--var", "api.name=a/b"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "path segment '{{ name }}' renders to 'a/b', which is not a file or folder name",
+        ));
+
+    // Two sources of a module may not produce the same file
+    ctx.write_file("modules/svc/templates/cmd/{{ env }}/main.go.j2", "x\n");
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["plan", "--no-input", "--var", "api.name=prod"])
+        .args(["--var", "api.env=prod"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("both produce 'cmd/prod/main.go'"));
+}
+
+#[test]
+fn test_files_rules_skip_files_and_plan_lists_them() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        r#"
+inputs:
+  name: { type: string }
+  publish: { type: bool, default: false }
+files:
+  - path: .github/workflows/release.yml
+    when: publish
+  - path: "docs/**"
+    when: "name != 'internal'"
+  - path: "*.txt"
+    when: publish
+"#,
+    );
+    ctx.write_file(
+        "modules/svc/templates/.github/workflows/release.yml.j2",
+        "name: release {{ name }}\n",
+    );
+    ctx.write_file("modules/svc/files/docs/index.md", "# Docs\n");
+    ctx.write_file("modules/svc/files/README.md", "readme\n");
+    ctx.write_file("modules/svc/files/NOTICE.txt", "notice\n");
+    ctx.write_file("modules/svc/files/licenses/mit.txt", "mit\n");
+    ctx.write_file("weaver.yaml", VARS_CONFIG);
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["plan", "--no-input", "--var", "api.name=internal"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Would skip svc/templates/.github/workflows/release.yml.j2 for app 'api': 'publish' is false",
+        ))
+        .stdout(predicate::str::contains(
+            "Would skip svc/files/docs/index.md for app 'api'",
+        ));
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=internal"])
+        .assert()
+        .success();
+    assert!(ctx.root.join("api/README.md").exists());
+    assert!(!ctx.root.join("api/.github").exists());
+    assert!(!ctx.root.join("api/docs").exists());
+    // `*` does not cross folders
+    assert!(!ctx.root.join("api/NOTICE.txt").exists());
+    assert!(ctx.root.join("api/licenses/mit.txt").exists());
+
+    cmd()
+        .current_dir(&ctx.root)
+        .env("HOME", ctx.root.as_os_str())
+        .args(["apply", "--no-input", "--var", "api.name=api"])
+        .args(["--var", "api.publish=true"])
+        .assert()
+        .success();
+    assert_eq!(
+        ctx.read_file("api/.github/workflows/release.yml"),
+        "name: release api\n"
+    );
+    assert!(ctx.root.join("api/docs/index.md").exists());
+    assert!(ctx.root.join("api/NOTICE.txt").exists());
+}
@@ -4,7 +4,7 @@ use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 pub fn cmd() -> Command {
-    Command::cargo_bin("rw").unwrap()
+    assert_cmd::cargo::cargo_bin_cmd!("rw")
 }
 
 pub struct TestContext {
@@ -13,6 +13,12 @@ pub struct TestContext {
     // Keep temp alive so dir isn't deleted until drop
 }
 
+impl Default for TestContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TestContext {
     pub fn new() -> Self {
         let temp = tempfile::tempdir().expect("failed to create temp dir");
@@ -33,6 +39,19 @@ impl TestContext {
     }
 
     pub fn setup_module(&self, name: &str, ref_: &str, content: &str) {
+        self.setup_repo(
+            name,
+            ref_,
+            &[
+                ("files/file.txt", content),
+                ("weaver.module.yaml", "inputs: {}"),
+            ],
+        );
+    }
+
+    /// Publishes `files` (relative path, content) as tag `ref_` of the bare
+    /// repository `remotes/<name>`.
+    pub fn setup_repo(&self, name: &str, ref_: &str, files: &[(&str, &str)]) {
         // Mocking a remote git repo is hard in pure integration tests without a real git server.
         // For MVP tests, we can use "file://" scheme if supported, or just mock the cache directly
         // if we want to cheat, but `rw apply` calls `git clone`.
@@ -46,7 +65,7 @@ impl TestContext {
 
         // Init remote repo
         std::process::Command::new("git")
-            .args(&["init", "--bare"])
+            .args(["init", "--bare"])
             .current_dir(&remote_path)
             .output()
             .unwrap();
@@ -56,19 +75,17 @@ impl TestContext {
         fs::create_dir_all(&source_path).unwrap();
 
         std::process::Command::new("git")
-            .args(&["init"])
+            .args(["init"])
             .current_dir(&source_path)
             .output()
             .unwrap();
 
         // Write content
-        let file_path = source_path.join("files/file.txt");
-        fs::create_dir_all(file_path.parent().unwrap()).unwrap();
-        fs::write(file_path, content).unwrap();
-
-        // Write manifest
-        let manifest = r#"inputs: {}"#;
-        fs::write(source_path.join("weaver.module.yaml"), manifest).unwrap();
+        for (rel, content) in files {
+            let file_path = source_path.join(rel);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, content).unwrap();
+        }
 
         // Commit (Git user config might be needed in CI)
         let git_envs = [
@@ -79,14 +96,14 @@ impl TestContext {
         ];
 
         std::process::Command::new("git")
-            .args(&["add", "."])
+            .args(["add", "."])
             .current_dir(&source_path)
             .envs(git_envs)
             .output()
             .unwrap();
 
         std::process::Command::new("git")
-            .args(&["commit", "-m", "Initial commit"])
+            .args(["commit", "-m", "Initial commit"])
             .current_dir(&source_path)
             .envs(git_envs)
             .output()
@@ -94,7 +111,7 @@ impl TestContext {
 
         // Tag it
         std::process::Command::new("git")
-            .args(&["tag", ref_])
+            .args(["tag", ref_])
             .current_dir(&source_path)
             .envs(git_envs)
             .output()
@@ -102,13 +119,13 @@ impl TestContext {
 
         // Push to bare remote
         std::process::Command::new("git")
-            .args(&["remote", "add", "origin", remote_path.to_str().unwrap()])
+            .args(["remote", "add", "origin", remote_path.to_str().unwrap()])
             .current_dir(&source_path)
             .output()
             .unwrap();
 
         std::process::Command::new("git")
-            .args(&["push", "origin", ref_])
+            .args(["push", "origin", ref_])
             .current_dir(&source_path)
             .output()
             .unwrap();
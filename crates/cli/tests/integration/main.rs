@@ -1,4 +1,10 @@
+mod answers;
 mod apply;
 pub mod common;
+mod migrate;
+mod schema;
+// Kept as written: uses `Command::cargo_bin`, deprecated since assert_cmd 2.1
+#[allow(deprecated, clippy::needless_borrows_for_generic_args)]
 mod update;
+mod validate;
 // Will add apply, update, run later
@@ -0,0 +1,50 @@
+use crate::common::{TestContext, cmd};
+use predicates::prelude::*;
+
+#[test]
+fn test_migrate_rewrites_outdated_files() {
+    let ctx = TestContext::new();
+    ctx.write_file("weaver.yaml", "version: \"1.0\"\nmodules: []\napps: []\n");
+    ctx.write_file(".rw/state.yaml", "files: {}\n");
+
+    cmd()
+        .current_dir(&ctx.root)
+        .args(["migrate", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+version: '1'"))
+        .stdout(predicate::str::contains("1 file(s) would be migrated"));
+    assert_eq!(ctx.read_file(".rw/state.yaml"), "files: {}\n");
+
+    cmd()
+        .current_dir(&ctx.root)
+        .args(["migrate", "--yes"])
+        .assert()
+        .success();
+    assert!(ctx.read_file(".rw/state.yaml").starts_with("version: '1'"));
+    // Already current, only spelled differently
+    assert_eq!(
+        ctx.read_file("weaver.yaml"),
+        "version: \"1.0\"\nmodules: []\napps: []\n"
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .arg("migrate")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("up to date"));
+}
+
+#[test]
+fn test_newer_config_version_is_rejected() {
+    let ctx = TestContext::new();
+    ctx.write_file("weaver.yaml", "version: \"7\"\napps: []\n");
+
+    cmd()
+        .current_dir(&ctx.root)
+        .arg("plan")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("newer than this rw supports"));
+}
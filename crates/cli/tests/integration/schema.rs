@@ -0,0 +1,26 @@
+use crate::common::cmd;
+
+#[test]
+fn test_checked_in_schemas_match_binary() {
+    for (target, file) in [
+        ("config", "weaver.schema.json"),
+        ("module", "weaver.module.schema.json"),
+    ] {
+        let output = cmd().args(["schema", target]).output().unwrap();
+        assert!(output.status.success());
+
+        let generated: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let checked_in: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(format!(
+                "../../specs/001-repo-weaver-mvp/contracts/{}",
+                file
+            ))
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            generated, checked_in,
+            "{file} is stale, regenerate it with `rw schema {target}`"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::common::{TestContext, cmd};
+use predicates::prelude::*;
+
+const INVALID_CONFIG: &str = r#"version: "1"
+modules: []
+apps:
+  - name: api
+    module: missing
+    path: api
+"#;
+
+#[test]
+fn test_validate_reports_location() {
+    let ctx = TestContext::new();
+    ctx.write_file("weaver.yaml", INVALID_CONFIG);
+
+    cmd()
+        .current_dir(&ctx.root)
+        .arg("validate")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Module 'missing' not found"))
+        .stderr(predicate::str::contains("weaver.yaml:5:13"));
+}
+
+#[test]
+fn test_validate_json_output() {
+    let ctx = TestContext::new();
+    ctx.write_file("weaver.yaml", INVALID_CONFIG);
+
+    let output = cmd()
+        .current_dir(&ctx.root)
+        .args(["--quiet", "--json", "validate"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["valid"], false);
+    assert_eq!(report["diagnostics"][0]["line"], 5);
+    assert_eq!(report["diagnostics"][0]["excerpt"], "    module: missing");
+}
+
+#[test]
+fn test_apply_runs_validation_first() {
+    let ctx = TestContext::new();
+    ctx.write_file("weaver.yaml", INVALID_CONFIG);
+
+    cmd()
+        .current_dir(&ctx.root)
+        .arg("apply")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Validation failed with 1 error(s)",
+        ));
+}
+
+#[test]
+fn test_validate_checks_input_types() {
+    let ctx = TestContext::new();
+    ctx.write_file(
+        "modules/svc/weaver.module.yaml",
+        "inputs:\n  replicas: { type: int }\n  enabled: { type: bool, default: false }\n",
+    );
+    ctx.write_file(
+        "weaver.yaml",
+        r#"version: "1"
+modules:
+  - { name: svc, source: ./modules/svc }
+apps:
+  - name: api
+    module: svc
+    path: api
+    inputs:
+      replicas: "three"
+      enabled: "true"
+"#,
+    );
+
+    cmd()
+        .current_dir(&ctx.root)
+        .arg("validate")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "App 'api': input 'replicas' of module 'svc': expected int, got \"three\"",
+        ))
+        .stderr(predicate::str::contains("weaver.yaml:9:7"))
+        .stderr(predicate::str::contains("input 'enabled'").not());
+}
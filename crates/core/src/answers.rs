@@ -0,0 +1,623 @@
+//! Saved answers to input prompts, stored per app in `.rw/answers/<app>.yaml`
+//! and keyed by the module reference of the app's layer:
+//!
+//! ```yaml
+//! go-service:
+//!   name: billing
+//! terraform.backend:
+//!   bucket: billing-state
+//! _origins:
+//!   go-service:
+//!     name: prompt
+//! ```
+//!
+//! The reserved `_origins` key records how each answer was obtained, see
+//! [`AnswerOrigin`]. Answers saved before origins were recorded have none.
+//!
+//! Answers of `sensitive` inputs are encrypted in the file, see [`crate::cipher`].
+//! They stay encrypted in memory and are only decrypted for the layer being
+//! resolved, so the key is only needed by apps with sensitive answers.
+//!
+//! Workspaces from before namespacing have a single flat `.rw/answers.yaml`.
+//! Its values are adopted by each app layer that declares the same input the
+//! first time the layer is resolved, and the file is removed on the next apply.
+
+use crate::cipher::{self, AnswerCipher};
+use crate::config::InputDef;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_yml::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Answers of one app: module reference -> input -> value.
+pub type AppAnswers = BTreeMap<String, BTreeMap<String, Value>>;
+
+/// Origins of the answers of one app: module reference -> input -> origin.
+type AppOrigins = BTreeMap<String, BTreeMap<String, AnswerOrigin>>;
+
+/// How a saved answer was obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnswerOrigin {
+    /// Entered at a prompt
+    Prompt,
+    /// Saved with `rw answers set`
+    Set,
+    /// Computed default, saved when not interactive
+    Computed,
+}
+
+/// Contents of an app answers file.
+#[derive(Default, Serialize, Deserialize)]
+struct AppFile {
+    #[serde(flatten)]
+    answers: AppAnswers,
+    #[serde(
+        rename = "_origins",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    origins: AppOrigins,
+}
+
+pub struct AnswerStore {
+    dir: PathBuf,
+    legacy_path: PathBuf,
+    apps: BTreeMap<String, AppAnswers>,
+    origins: BTreeMap<String, AppOrigins>,
+    legacy: Option<HashMap<String, Value>>,
+    /// Apps whose file must be rewritten
+    dirty: Vec<String>,
+    /// Answers (app, module, input) stored encrypted
+    sensitive: HashSet<(String, String, String)>,
+    key_file: PathBuf,
+    cipher: Option<AnswerCipher>,
+}
+
+impl AnswerStore {
+    /// Opens the answers kept in the `.rw` folder `rw_dir`.
+    pub fn open(rw_dir: &Path) -> anyhow::Result<Self> {
+        Self::open_with_key_file(rw_dir, cipher::default_key_file()?)
+    }
+
+    /// Opens the answers kept in `rw_dir`, with sensitive answers encrypted
+    /// under the key in `key_file`.
+    pub fn open_with_key_file(rw_dir: &Path, key_file: PathBuf) -> anyhow::Result<Self> {
+        let dir = rw_dir.join("answers");
+        let legacy_path = rw_dir.join("answers.yaml");
+
+        let mut apps = BTreeMap::new();
+        if dir.is_dir() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.extension().is_none_or(|ext| ext != "yaml") {
+                    continue;
+                }
+                let Some(stem) = path.file_stem().map(|s| s.to_string_lossy()) else {
+                    continue;
+                };
+                let app = urlencoding::decode(&stem)?.into_owned();
+                let content = fs::read_to_string(&path)?;
+                let file: AppFile = serde_yml::from_str(&content)
+                    .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+                apps.insert(app, file);
+            }
+        }
+
+        let legacy = if legacy_path.exists() {
+            let content = fs::read_to_string(&legacy_path)?;
+            Some(
+                serde_yml::from_str(&content)
+                    .map_err(|e| anyhow::anyhow!("{}: {}", legacy_path.display(), e))?,
+            )
+        } else {
+            None
+        };
+
+        let mut store = Self {
+            dir,
+            legacy_path,
+            apps: BTreeMap::new(),
+            origins: BTreeMap::new(),
+            legacy,
+            dirty: Vec::new(),
+            sensitive: HashSet::new(),
+            key_file,
+            cipher: None,
+        };
+        for (app, AppFile { answers, origins }) in apps {
+            for (module, layer) in &answers {
+                for (key, value) in layer {
+                    if cipher::is_encrypted(value) {
+                        store
+                            .sensitive
+                            .insert((app.clone(), module.clone(), key.clone()));
+                    }
+                }
+            }
+            store.origins.insert(app.clone(), origins);
+            store.apps.insert(app, answers);
+        }
+        Ok(store)
+    }
+
+    /// Saved answers of the layer `module` of `app`, for the module's
+    /// `inputs`, decrypted. Fails when the layer has encrypted answers and
+    /// the key is missing.
+    pub fn layer_answers(
+        &mut self,
+        app: &str,
+        module: &str,
+        inputs: &IndexMap<String, InputDef>,
+    ) -> anyhow::Result<HashMap<String, Value>> {
+        self.saved_layer(app, module, inputs)
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self
+                    .decrypted(&value)
+                    .map_err(|e| anyhow::anyhow!("Answer '{}' of app '{}': {}", key, app, e))?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    /// Saved answers of the layer `module` of `app` as stored, sensitive
+    /// ones still encrypted. Answers saved under a name listed in an input's
+    /// `renamed_from` move to the input's current name. Answers from the
+    /// legacy flat file are adopted for inputs the layer has no answer for yet.
+    pub fn saved_layer(
+        &mut self,
+        app: &str,
+        module: &str,
+        inputs: &IndexMap<String, InputDef>,
+    ) -> BTreeMap<String, Value> {
+        let mut renamed = false;
+        if let Some(layer) = self
+            .apps
+            .get_mut(app)
+            .and_then(|answers| answers.get_mut(module))
+        {
+            for (key, def) in inputs {
+                for old in &def.renamed_from {
+                    if !layer.contains_key(key)
+                        && let Some(value) = layer.remove(old)
+                    {
+                        layer.insert(key.clone(), value);
+                        if let Some(origins) = self
+                            .origins
+                            .get_mut(app)
+                            .and_then(|origins| origins.get_mut(module))
+                            && let Some(origin) = origins.remove(old)
+                        {
+                            origins.insert(key.clone(), origin);
+                        }
+                        renamed = true;
+                    }
+                }
+            }
+        }
+        if renamed {
+            self.mark_dirty(app);
+        }
+
+        // Answers saved before their input became sensitive get encrypted
+        for key in inputs
+            .iter()
+            .filter(|(_, def)| def.sensitive)
+            .map(|(key, _)| key)
+        {
+            let entry = (app.to_string(), module.to_string(), key.clone());
+            if self.sensitive.insert(entry)
+                && self
+                    .apps
+                    .get(app)
+                    .and_then(|answers| answers.get(module))
+                    .is_some_and(|layer| layer.contains_key(key))
+            {
+                self.mark_dirty(app);
+            }
+        }
+
+        if let Some(legacy) = &self.legacy {
+            let existing = self.apps.get(app).and_then(|answers| answers.get(module));
+            let adopted: HashMap<String, Value> = inputs
+                .iter()
+                .filter(|(key, _)| existing.is_none_or(|layer| !layer.contains_key(*key)))
+                .filter_map(|(key, def)| {
+                    let value = std::iter::once(key)
+                        .chain(&def.renamed_from)
+                        .find_map(|name| legacy.get(name))?;
+                    Some((key.clone(), value.clone()))
+                })
+                .collect();
+            self.insert(app, module, adopted);
+        }
+
+        self.apps
+            .get(app)
+            .and_then(|answers| answers.get(module))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// `value` as saved, decrypted when it is an encrypted answer.
+    pub fn decrypted(&mut self, value: &Value) -> anyhow::Result<Value> {
+        if cipher::is_encrypted(value) {
+            self.cipher(false)?.decrypt(value)
+        } else {
+            Ok(value.clone())
+        }
+    }
+
+    /// All saved answers, by app. Sensitive answers are encrypted, see
+    /// [`AnswerStore::decrypted`].
+    pub fn apps(&self) -> &BTreeMap<String, AppAnswers> {
+        &self.apps
+    }
+
+    /// Whether the answer `input` of the layer `module` of `app` is stored
+    /// encrypted.
+    pub fn is_sensitive(&self, app: &str, module: &str, input: &str) -> bool {
+        self.sensitive
+            .contains(&(app.to_string(), module.to_string(), input.to_string()))
+    }
+
+    /// How the answer `input` of the layer `module` of `app` was obtained,
+    /// if recorded.
+    pub fn origin(&self, app: &str, module: &str, input: &str) -> Option<AnswerOrigin> {
+        self.origins.get(app)?.get(module)?.get(input).copied()
+    }
+
+    /// Records new answers for the layer `module` of `app`, obtained from
+    /// `origin`.
+    pub fn record(
+        &mut self,
+        app: &str,
+        module: &str,
+        answers: HashMap<String, Value>,
+        origin: AnswerOrigin,
+    ) {
+        if answers.is_empty() {
+            return;
+        }
+        let origins = self
+            .origins
+            .entry(app.to_string())
+            .or_default()
+            .entry(module.to_string())
+            .or_default();
+        origins.extend(answers.keys().map(|key| (key.clone(), origin)));
+        self.insert(app, module, answers);
+    }
+
+    /// Adds answers to the layer `module` of `app`, without an origin.
+    fn insert(&mut self, app: &str, module: &str, answers: HashMap<String, Value>) {
+        if answers.is_empty() {
+            return;
+        }
+        self.apps
+            .entry(app.to_string())
+            .or_default()
+            .entry(module.to_string())
+            .or_default()
+            .extend(answers);
+        self.mark_dirty(app);
+    }
+
+    /// Answers of `app` that match no current input: answers of modules the
+    /// app no longer uses and of inputs its modules no longer declare.
+    /// `layers` maps each module reference of the app to its declared inputs.
+    pub fn stale_answers(
+        &self,
+        app: &str,
+        layers: &HashMap<String, Vec<String>>,
+    ) -> Vec<(String, String)> {
+        let Some(answers) = self.apps.get(app) else {
+            return Vec::new();
+        };
+        answers
+            .iter()
+            .flat_map(|(module, layer)| {
+                let declared = layers.get(module);
+                layer
+                    .keys()
+                    .filter(move |key| declared.is_none_or(|inputs| !inputs.contains(key)))
+                    .map(move |key| (module.clone(), key.clone()))
+            })
+            .collect()
+    }
+
+    /// Deletes the answers `(module, input)` of `app`.
+    pub fn remove(&mut self, app: &str, entries: &[(String, String)]) {
+        let Some(answers) = self.apps.get_mut(app) else {
+            return;
+        };
+        let origins = self.origins.entry(app.to_string()).or_default();
+        for (module, key) in entries {
+            if let Some(layer) = origins.get_mut(module) {
+                layer.remove(key);
+                if layer.is_empty() {
+                    origins.remove(module);
+                }
+            }
+            if let Some(layer) = answers.get_mut(module) {
+                layer.remove(key);
+                if layer.is_empty() {
+                    answers.remove(module);
+                }
+            }
+        }
+        self.mark_dirty(app);
+    }
+
+    /// Forgets the answers of apps not in `apps`, e.g. apps removed from
+    /// `weaver.yaml`. Returns the names of the forgotten apps.
+    pub fn retain_apps(&mut self, apps: &[&str]) -> Vec<String> {
+        let removed: Vec<String> = self
+            .apps
+            .keys()
+            .filter(|app| !apps.contains(&app.as_str()))
+            .cloned()
+            .collect();
+        for app in &removed {
+            self.apps.remove(app);
+            self.origins.remove(app);
+            self.mark_dirty(app);
+        }
+        removed
+    }
+
+    /// Writes changed app files. With `finish_migration`, the legacy flat
+    /// file is removed, as every app had the chance to adopt its answers.
+    /// Sensitive answers that are still encrypted keep their ciphertext, so
+    /// unchanged values do not churn the file.
+    pub fn save(&mut self, finish_migration: bool) -> anyhow::Result<()> {
+        for app in std::mem::take(&mut self.dirty) {
+            let path = self.app_path(&app);
+            match self.apps.get(&app).filter(|answers| !answers.is_empty()) {
+                Some(answers) => {
+                    let mut stored = answers.clone();
+                    for (module, layer) in &mut stored {
+                        for (key, value) in layer.iter_mut() {
+                            let entry = (app.clone(), module.clone(), key.clone());
+                            if self.sensitive.contains(&entry) && !cipher::is_encrypted(value) {
+                                // A new key would leave existing ciphertexts unreadable
+                                let create = !self.has_ciphertext();
+                                *value = self.cipher(create)?.encrypt(value)?;
+                            }
+                        }
+                    }
+                    let file = AppFile {
+                        answers: stored,
+                        origins: self.origins.get(&app).cloned().unwrap_or_default(),
+                    };
+                    fs::create_dir_all(&self.dir)?;
+                    fs::write(&path, serde_yml::to_string(&file)?)?;
+                }
+                None if path.exists() => fs::remove_file(&path)?,
+                None => {}
+            }
+        }
+
+        if finish_migration && self.legacy.take().is_some() {
+            fs::remove_file(&self.legacy_path)?;
+        }
+        Ok(())
+    }
+
+    fn has_ciphertext(&self) -> bool {
+        self.apps
+            .values()
+            .flat_map(|answers| answers.values())
+            .flat_map(|layer| layer.values())
+            .any(cipher::is_encrypted)
+    }
+
+    /// The answers cipher, loading its key on first use.
+    fn cipher(&mut self, create: bool) -> anyhow::Result<&AnswerCipher> {
+        if self.cipher.is_none() {
+            self.cipher = Some(AnswerCipher::load(&self.key_file, create)?);
+        }
+        Ok(self.cipher.as_ref().unwrap())
+    }
+
+    fn app_path(&self, app: &str) -> PathBuf {
+        self.dir.join(format!("{}.yaml", urlencoding::encode(app)))
+    }
+
+    fn mark_dirty(&mut self, app: &str) {
+        if !self.dirty.iter().any(|a| a == app) {
+            self.dirty.push(app.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(names: &[&str]) -> IndexMap<String, InputDef> {
+        names
+            .iter()
+            .map(|name| {
+                let def = serde_yml::from_str("{ type: string }").unwrap();
+                (name.to_string(), def)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_answers_are_namespaced_per_app_and_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = AnswerStore::open(dir.path()).unwrap();
+        store.record(
+            "api",
+            "svc",
+            [("name".to_string(), Value::from("api"))].into(),
+            AnswerOrigin::Prompt,
+        );
+        store.record(
+            "worker",
+            "svc",
+            [("name".to_string(), Value::from("worker"))].into(),
+            AnswerOrigin::Prompt,
+        );
+        store.save(true).unwrap();
+
+        let mut store = AnswerStore::open(dir.path()).unwrap();
+        assert_eq!(
+            store.origin("api", "svc", "name"),
+            Some(AnswerOrigin::Prompt)
+        );
+        assert_eq!(
+            store
+                .layer_answers("api", "svc", &inputs(&["name"]))
+                .unwrap()["name"],
+            Value::from("api")
+        );
+        assert!(
+            store
+                .layer_answers("api", "other", &inputs(&["name"]))
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(store.retain_apps(&["api"]), vec!["worker".to_string()]);
+        store.save(true).unwrap();
+        assert!(!dir.path().join("answers/worker.yaml").exists());
+        assert!(dir.path().join("answers/api.yaml").exists());
+    }
+
+    #[test]
+    fn test_legacy_flat_file_is_adopted() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("answers.yaml"),
+            "name: shared\nregion: eu-west-1\n",
+        )
+        .unwrap();
+
+        let mut store = AnswerStore::open(dir.path()).unwrap();
+        let answers = store
+            .layer_answers("api", "svc", &inputs(&["name"]))
+            .unwrap();
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers["name"], Value::from("shared"));
+        store.save(true).unwrap();
+
+        assert!(!dir.path().join("answers.yaml").exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("answers/api.yaml")).unwrap(),
+            "svc:\n  name: shared\n"
+        );
+    }
+
+    #[test]
+    fn test_renamed_and_stale_answers() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = AnswerStore::open(dir.path()).unwrap();
+        store.record(
+            "api",
+            "svc",
+            [
+                ("region".to_string(), Value::from("eu-west-1")),
+                ("legacy_flag".to_string(), Value::Bool(true)),
+            ]
+            .into(),
+            AnswerOrigin::Computed,
+        );
+        store.record(
+            "api",
+            "dropped",
+            [("x".to_string(), Value::from(1))].into(),
+            AnswerOrigin::Set,
+        );
+
+        let mut current = inputs(&["aws_region"]);
+        current["aws_region"].renamed_from = vec!["region".to_string()];
+        let answers = store.layer_answers("api", "svc", &current).unwrap();
+        assert_eq!(answers["aws_region"], Value::from("eu-west-1"));
+        assert_eq!(
+            store.origin("api", "svc", "aws_region"),
+            Some(AnswerOrigin::Computed)
+        );
+        assert_eq!(store.origin("api", "svc", "region"), None);
+
+        let layers: HashMap<String, Vec<String>> =
+            [("svc".to_string(), vec!["aws_region".to_string()])].into();
+        let stale = store.stale_answers("api", &layers);
+        assert_eq!(
+            stale,
+            vec![
+                ("dropped".to_string(), "x".to_string()),
+                ("svc".to_string(), "legacy_flag".to_string())
+            ]
+        );
+        store.remove("api", &stale);
+        assert!(store.stale_answers("api", &layers).is_empty());
+    }
+
+    #[test]
+    fn test_sensitive_answers_are_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let key_file = dir.path().join("keys/answers.key");
+        // A plain answer saved before the input became sensitive
+        fs::create_dir_all(dir.path().join("answers")).unwrap();
+        fs::write(
+            dir.path().join("answers/api.yaml"),
+            "svc:\n  name: api\n  token: t0k3n\n",
+        )
+        .unwrap();
+
+        let mut current = inputs(&["name", "token"]);
+        current["token"].sensitive = true;
+        let mut store = AnswerStore::open_with_key_file(dir.path(), key_file.clone()).unwrap();
+        store.layer_answers("api", "svc", &current).unwrap();
+        store.save(true).unwrap();
+
+        let content = fs::read_to_string(dir.path().join("answers/api.yaml")).unwrap();
+        assert!(content.contains("name: api"), "{content}");
+        assert!(content.contains("token: enc:v1:"), "{content}");
+        assert!(!content.contains("t0k3n"), "{content}");
+        assert!(key_file.exists());
+
+        let mut store = AnswerStore::open_with_key_file(dir.path(), key_file).unwrap();
+        assert_eq!(
+            store.layer_answers("api", "svc", &current).unwrap()["token"],
+            Value::from("t0k3n")
+        );
+
+        // Unchanged sensitive answers keep their ciphertext
+        store.record(
+            "api",
+            "svc",
+            [("name".to_string(), Value::from("billing"))].into(),
+            AnswerOrigin::Prompt,
+        );
+        store.save(true).unwrap();
+        let resaved = fs::read_to_string(dir.path().join("answers/api.yaml")).unwrap();
+        assert_eq!(
+            resaved.lines().find(|l| l.contains("token")),
+            content.lines().find(|l| l.contains("token"))
+        );
+
+        // Without the key, only the layers with encrypted answers fail
+        let mut store =
+            AnswerStore::open_with_key_file(dir.path(), dir.path().join("missing.key")).unwrap();
+        store.record(
+            "worker",
+            "svc",
+            [("name".to_string(), Value::from("worker"))].into(),
+            AnswerOrigin::Prompt,
+        );
+        store.save(true).unwrap();
+        assert_eq!(
+            store.layer_answers("worker", "svc", &current).unwrap()["name"],
+            Value::from("worker")
+        );
+        let err = store.layer_answers("api", "svc", &current).unwrap_err();
+        assert!(err.to_string().contains("no key was found"), "{err}");
+    }
+}
@@ -1,4 +1,6 @@
 use crate::config::{AppConfig, ModuleManifest};
+use crate::input::{self, InputSpec};
+use crate::secret::Secret;
 use std::collections::HashMap;
 use std::path::PathBuf;
 
@@ -6,28 +8,85 @@ pub struct App {
     pub name: String,
     pub path: PathBuf,
     pub inputs: HashMap<String, serde_yml::Value>,
+    /// Inputs built from `${secret:...}` references
+    pub secrets: HashMap<String, Secret<serde_yml::Value>>,
 }
 
 impl App {
-    pub fn instantiate(config: &AppConfig, manifest: &ModuleManifest) -> anyhow::Result<Self> {
+    /// Instantiates `config` as built from `module` (a reference from the
+    /// app's layers) with the given `inputs` and `secret_inputs`.
+    pub fn instantiate(
+        config: &AppConfig,
+        module: &str,
+        inputs: &HashMap<String, serde_yml::Value>,
+        mut secret_inputs: HashMap<String, Secret<serde_yml::Value>>,
+        manifest: &ModuleManifest,
+    ) -> anyhow::Result<Self> {
         let mut final_inputs = HashMap::new();
+        let mut secrets = HashMap::new();
+        let fail = |key: &str, e: anyhow::Error| {
+            anyhow::anyhow!(
+                "App '{}': input '{}' of module '{}': {}",
+                config.name,
+                key,
+                module,
+                e
+            )
+        };
+
+        // Conditions and templated defaults see the inputs resolved before
+        // them; skipped or unset inputs are null
+        let mut context: HashMap<String, serde_yml::Value> = manifest
+            .inputs
+            .keys()
+            .map(|key| (key.clone(), serde_yml::Value::Null))
+            .collect();
+        let order = input::resolution_order(&manifest.inputs)
+            .map_err(|e| anyhow::anyhow!("Module '{}': {}", module, e))?;
+        for key in &order {
+            let def = &manifest.inputs[key];
+            let spec = InputSpec::of(def).map_err(|e| fail(key, e))?;
+
+            if !input::is_active(def, &context).map_err(|e| fail(key, e))? {
+                continue;
+            }
+
+            if let Some(secret) = secret_inputs.remove(key) {
+                // The message must not echo the secret value
+                let value = spec.check(secret.expose()).map_err(|_| {
+                    anyhow::anyhow!(
+                        "App '{}': input '{}' of module '{}': secret value is not a valid {} or breaks a validation rule",
+                        config.name,
+                        key,
+                        module,
+                        spec.ty
+                    )
+                })?;
+                secrets.insert(key.clone(), Secret::new(value));
+                continue;
+            }
 
-        for (key, def) in &manifest.inputs {
             // Check provided input OR default
-            let val = config.inputs.get(key).or(def.default.as_ref());
+            let val = match inputs.get(key) {
+                Some(v) => Some(v.clone()),
+                None => input::default_value(def, &context)
+                    .map_err(|e| fail(key, e))?
+                    .map(|(v, _)| v),
+            };
 
             if val.is_none() && def.required {
                 anyhow::bail!(
                     "App '{}': Missing required input for module '{}': {}",
                     config.name,
-                    config.module,
+                    module,
                     key
                 );
             }
 
             if let Some(v) = val {
-                // TODO: Type checking against def.type
-                final_inputs.insert(key.clone(), v.clone());
+                let value = spec.check(&v).map_err(|e| fail(key, e))?;
+                context.insert(key.clone(), value.clone());
+                final_inputs.insert(key.clone(), value);
             }
         }
 
@@ -41,6 +100,7 @@ impl App {
             name: config.name.clone(),
             path: PathBuf::from(&config.path),
             inputs: final_inputs,
+            secrets,
         })
     }
 }
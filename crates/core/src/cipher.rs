@@ -0,0 +1,115 @@
+//! Encryption of sensitive answers at rest.
+//!
+//! Values are encrypted with ChaCha20-Poly1305 under a 32-byte key and
+//! stored as `enc:v1:<base64 of nonce and ciphertext>`. The key comes from
+//! `RW_ANSWERS_KEY` (base64), else from the key file (`RW_ANSWERS_KEY_FILE`,
+//! default `~/.rw/answers.key`), which is created on first use. The key file
+//! lives outside the workspace, so answers files can be committed.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde_yml::Value;
+use std::path::{Path, PathBuf};
+
+const PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+
+/// Whether `value` is an encrypted answer.
+pub fn is_encrypted(value: &Value) -> bool {
+    matches!(value, Value::String(s) if s.starts_with(PREFIX))
+}
+
+/// The key file: `RW_ANSWERS_KEY_FILE`, else `~/.rw/answers.key`.
+pub fn default_key_file() -> anyhow::Result<PathBuf> {
+    if let Ok(path) = std::env::var("RW_ANSWERS_KEY_FILE") {
+        return Ok(PathBuf::from(path));
+    }
+    let home = home::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
+    Ok(home.join(".rw").join("answers.key"))
+}
+
+pub struct AnswerCipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl AnswerCipher {
+    /// Loads the key, from `RW_ANSWERS_KEY` or `key_file`. With `create`, a
+    /// missing key file is generated; otherwise a missing key is an error.
+    pub fn load(key_file: &Path, create: bool) -> anyhow::Result<Self> {
+        let encoded = match std::env::var("RW_ANSWERS_KEY") {
+            Ok(key) => key,
+            Err(_) if key_file.exists() => std::fs::read_to_string(key_file)
+                .map_err(|e| anyhow::anyhow!("{}: {}", key_file.display(), e))?,
+            Err(_) if create => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                let encoded = BASE64.encode(key);
+                write_key_file(key_file, &encoded)?;
+                tracing::info!(
+                    "Created answers key {}; share it to decrypt sensitive answers elsewhere",
+                    key_file.display()
+                );
+                encoded
+            }
+            Err(_) => anyhow::bail!(
+                "sensitive answers are encrypted, but no key was found: set RW_ANSWERS_KEY or provide {}",
+                key_file.display()
+            ),
+        };
+        let key = BASE64
+            .decode(encoded.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| anyhow::anyhow!("answers key must be 32 bytes, base64 encoded"))?;
+        Ok(Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+        })
+    }
+
+    pub fn encrypt(&self, value: &Value) -> anyhow::Result<Value> {
+        let plain = serde_json::to_vec(value)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let sealed = self
+            .cipher
+            .encrypt(&nonce, plain.as_slice())
+            .map_err(|_| anyhow::anyhow!("failed to encrypt answer"))?;
+        let mut payload = nonce.to_vec();
+        payload.extend(sealed);
+        Ok(Value::String(format!(
+            "{}{}",
+            PREFIX,
+            BASE64.encode(payload)
+        )))
+    }
+
+    pub fn decrypt(&self, value: &Value) -> anyhow::Result<Value> {
+        let Value::String(text) = value else {
+            anyhow::bail!("not an encrypted answer");
+        };
+        let payload = text
+            .strip_prefix(PREFIX)
+            .and_then(|encoded| BASE64.decode(encoded).ok())
+            .filter(|payload| payload.len() > NONCE_LEN)
+            .ok_or_else(|| anyhow::anyhow!("malformed encrypted answer"))?;
+        let (nonce, sealed) = payload.split_at(NONCE_LEN);
+        let plain = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), sealed)
+            .map_err(|_| anyhow::anyhow!("cannot decrypt answer: wrong answers key?"))?;
+        Ok(serde_json::from_slice(&plain)?)
+    }
+}
+
+fn write_key_file(path: &Path, encoded: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, format!("{}\n", encoded))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
@@ -1,78 +1,381 @@
+use crate::input::{self, InputSpec};
+use crate::interpolate;
+use crate::loader;
+use crate::migrate::{self, DocumentKind};
+use crate::template;
+use indexmap::IndexMap;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Root workspace configuration (`weaver.yaml`).
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WeaverConfig {
+    /// Config schema version.
     pub version: String,
+    /// Config fragments (files or globs, relative to the root file) merged
+    /// after the root file. `weaver.d/**/*.yaml` is always included.
+    #[serde(default)]
+    pub includes: Vec<String>,
+    /// Modules apps can be built from.
     #[serde(default)]
     pub modules: Vec<ModuleConfig>,
+    /// App instances managed in this workspace.
     #[serde(default)]
     pub apps: Vec<AppConfig>,
+    /// Logical secret names mapped to the provider that resolves them.
     #[serde(default)]
     pub secrets: HashMap<String, SecretConfig>,
+    /// Discovery of app-local `weaver.app.yaml` files.
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    /// Files the config was merged from, in load order.
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
 }
 
 impl WeaverConfig {
-    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        let config: Self = serde_yml::from_str(&content)?;
+    /// Loads the root config and merges its fragments, see [`crate::loader`].
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let merged = loader::load_merged(path)?;
+        let mut config: Self = serde_yml::from_value(merged.value).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid config (merged from {}): {}",
+                merged
+                    .sources
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                e
+            )
+        })?;
+        for app in &mut config.apps {
+            app.origin = merged.app_origins.get(&app.name).cloned();
+        }
+        config.sources = merged.sources;
+        interpolate::interpolate_config(&mut config, &|name| std::env::var(name).ok())?;
         Ok(config)
     }
+
+    /// Resolves a `module` or `module.block` reference to its module and
+    /// optional block name.
+    pub fn find_module(&self, reference: &str) -> anyhow::Result<(&ModuleConfig, Option<String>)> {
+        if let Some(module) = self.modules.iter().find(|m| m.name == reference) {
+            return Ok((module, None));
+        }
+        if let Some((name, block)) = reference.rsplit_once('.')
+            && let Some(module) = self.modules.iter().find(|m| m.name == name)
+        {
+            return Ok((module, Some(block.to_string())));
+        }
+        anyhow::bail!("Module '{}' not found", reference)
+    }
+}
+
+/// JSON Schema of `weaver.yaml` (and its fragments), generated from [`WeaverConfig`].
+pub fn config_schema() -> serde_json::Value {
+    schemars::schema_for!(WeaverConfig).to_value()
+}
+
+/// JSON Schema of `weaver.module.yaml`, generated from [`ModuleManifest`].
+pub fn module_schema() -> serde_json::Value {
+    schemars::schema_for!(ModuleManifest).to_value()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Opt-in discovery of app-local `weaver.app.yaml` files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DiscoveryConfig {
+    /// Search the workspace for `weaver.app.yaml` files.
+    #[serde(default)]
+    pub enabled: bool,
+    /// Globs (relative to the workspace root) that are not searched. `*`
+    /// stays within one folder, `**` crosses folders. Hidden folders,
+    /// `node_modules` and `target` are never searched.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// A module source pinned to a ref.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ModuleConfig {
+    /// Name apps use to reference the module.
     pub name: String,
+    /// Git URL of the module repository, or a local directory (`./dir`,
+    /// `../dir`, an absolute path or `path:<dir>`) read in place.
     pub source: String,
-    pub r#ref: String, // "ref" is a keyword in some languages, but okay in Rust struct field? No, "ref" is keyword. Use raw identifier.
+    /// Tag, branch or commit to check out. Required for git sources.
+    #[serde(default)]
+    pub r#ref: Option<String>, // "ref" is a keyword, hence the raw identifier
+    /// Subdirectory of the source containing the module.
     #[serde(default)]
     pub path: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An app instance bound to a folder of the workspace.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AppConfig {
+    /// Unique app name.
     pub name: String,
-    pub module: String,
+    /// Shorthand for an app built from a single module.
+    #[serde(default)]
+    pub module: Option<String>,
+    /// Modules (`module`) or module blocks (`module.block`, read from the
+    /// module's `blocks/<block>/` folder) composed into this app, applied in
+    /// order after `module`. Each may be listed once.
+    #[serde(default)]
+    pub extends: Vec<ExtendsEntry>,
+    /// Folder of the app, relative to the workspace root.
     pub path: String,
+    /// Inputs shared by every module of the app.
+    #[serde(default)]
+    #[schemars(with = "HashMap<String, serde_json::Value>")]
+    pub inputs: HashMap<String, serde_yml::Value>,
+    /// Destination file (relative to `path`) -> module reference whose copy
+    /// wins when several modules produce that file.
     #[serde(default)]
+    pub prefer: HashMap<String, String>,
+    /// Config file that declared this app.
+    #[serde(skip)]
+    pub origin: Option<PathBuf>,
+}
+
+impl AppConfig {
+    /// Module references this app is built from, in application order.
+    /// Fails when a reference is listed twice, as its files would be
+    /// rendered twice.
+    pub fn layers(&self) -> anyhow::Result<Vec<AppLayer>> {
+        let mut layers: Vec<AppLayer> = Vec::new();
+        if let Some(module) = &self.module {
+            layers.push(AppLayer {
+                reference: module.clone(),
+                inputs: self.inputs.clone(),
+            });
+        }
+        for entry in &self.extends {
+            let (reference, own_inputs) = match entry {
+                ExtendsEntry::Reference(reference) => (reference, None),
+                ExtendsEntry::Detailed { module, inputs } => (module, Some(inputs)),
+            };
+            if layers.iter().any(|layer| layer.reference == *reference) {
+                anyhow::bail!("App '{}' lists '{}' more than once", self.name, reference);
+            }
+            let mut inputs = self.inputs.clone();
+            if let Some(own_inputs) = own_inputs {
+                inputs.extend(own_inputs.clone());
+            }
+            layers.push(AppLayer {
+                reference: reference.clone(),
+                inputs,
+            });
+        }
+        Ok(layers)
+    }
+}
+
+/// One entry of an app's `extends:` list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ExtendsEntry {
+    /// `moduleA` or `moduleB.block`, where `block` is the module's
+    /// `blocks/<block>/` folder
+    Reference(String),
+    /// A reference with inputs only passed to that module. They take
+    /// precedence over the app's shared `inputs`.
+    Detailed {
+        /// `module` or `module.block` reference.
+        module: String,
+        /// Inputs only passed to this module.
+        #[serde(default)]
+        #[schemars(with = "HashMap<String, serde_json::Value>")]
+        inputs: HashMap<String, serde_yml::Value>,
+    },
+}
+
+/// A module applied to an app, with the inputs it receives.
+#[derive(Debug, Clone)]
+pub struct AppLayer {
+    pub reference: String,
     pub inputs: HashMap<String, serde_yml::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Where a secret is resolved from.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SecretConfig {
+    /// Provider name, e.g. `env` or a plugin such as `aws-ssm`.
     pub provider: String,
+    /// Provider-specific key of the secret.
     pub key: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Module manifest (`weaver.module.yaml`), at the root of the module next
+/// to its `files/` and `templates/`. A block, referenced by apps as
+/// `module.block`, is a `blocks/<block>/` folder with its own `files/` and
+/// `templates/`, and the inputs of this manifest.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ModuleManifest {
+    /// Manifest schema version, defaults to the first versioned schema.
     #[serde(default)]
-    pub inputs: HashMap<String, InputDef>,
+    pub version: Option<String>,
+    /// Inputs the module accepts, keyed by name, in declaration order.
+    #[serde(default)]
+    pub inputs: IndexMap<String, InputDef>,
+    /// Values the module exposes, keyed by name.
     #[serde(default)]
     pub outputs: HashMap<String, String>,
+    /// Tasks runnable with `rw run <app> <task>`.
     #[serde(default)]
     pub tasks: HashMap<String, TaskDef>,
+    /// Conditions on the files the module emits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileRule>,
+}
+
+/// Emits the files matching `path` only when `when` holds.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FileRule {
+    /// Glob over destination paths, relative to the app folder
+    /// (`.github/workflows/release.yml`, `docs/**`).
+    pub path: String,
+    /// Condition over the inputs, as for inputs: a bare expression
+    /// (`publish`, `cloud == 'aws'`) or a template.
+    pub when: String,
+}
+
+impl FileRule {
+    /// Whether the rule applies to the destination `path`. `*` and `?`
+    /// stay within one folder; `**` crosses folders.
+    pub fn matches(&self, path: &Path) -> bool {
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        glob::Pattern::new(&self.path).is_ok_and(|pattern| pattern.matches_path_with(path, options))
+    }
 }
 
 impl ModuleManifest {
-    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
         let content = std::fs::read_to_string(path)?;
-        let manifest: Self = serde_yml::from_str(&content)?;
+        let doc: serde_yml::Value = serde_yml::from_str(&content)?;
+        migrate::check_version(DocumentKind::Manifest, &doc, path, false)?;
+        let manifest: Self = serde_yml::from_value(doc)?;
+
+        input::resolution_order(&manifest.inputs)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        for rule in &manifest.files {
+            glob::Pattern::new(&rule.path).map_err(|e| {
+                anyhow::anyhow!(
+                    "{}: invalid files path '{}': {}",
+                    path.display(),
+                    rule.path,
+                    e
+                )
+            })?;
+        }
+        for (key, def) in &manifest.inputs {
+            if template::is_reserved(key) {
+                anyhow::bail!(
+                    "{}: input '{}' uses a name reserved by the template context",
+                    path.display(),
+                    key
+                );
+            }
+            let spec = InputSpec::of(def)
+                .and_then(|spec| input::check_derivation(def).map(|_| spec))
+                .map_err(|e| anyhow::anyhow!("{}: input '{}': {}", path.display(), key, e))?;
+            // Templated defaults are checked once rendered
+            if let Some(default) = &def.default
+                && !matches!(default, serde_yml::Value::String(s) if input::is_template(s))
+            {
+                spec.check(default).map_err(|e| {
+                    anyhow::anyhow!("{}: default of input '{}': {}", path.display(), key, e)
+                })?;
+            }
+        }
         Ok(manifest)
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Declaration of a module input.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct InputDef {
+    /// Value type: `string`, `int`, `float`, `bool`, `list<T>`, `map` or `enum`.
     pub r#type: String,
+    /// Allowed values of an `enum` (or `list<enum>`) input.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+    /// Value used when the app provides none. A string template
+    /// (`"{{ name }}-state"`) is rendered from the other inputs.
+    #[schemars(with = "Option<serde_json::Value>")]
     pub default: Option<serde_yml::Value>,
+    /// Help text shown when prompting.
     pub description: Option<String>,
+    /// Fail when no value is resolved.
     #[serde(default)]
     pub required: bool,
+    /// Hide the value when prompting for it and encrypt its saved answer.
+    #[serde(default)]
+    pub sensitive: bool,
+    /// Program and arguments whose output is the input's value, e.g.
+    /// `[git, config, user.email]`. Run in the app folder; the input is never
+    /// prompted for and its value is not saved.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub from_command: Vec<String>,
+    /// JSON pointer (`/bucket/value`) selecting the value from the command's
+    /// JSON output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_pointer: Option<String>,
+    /// Regex matched against the command's output; the value is the first
+    /// capture group, or the whole match without groups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    /// Former names of the input; saved answers under these names carry over.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub renamed_from: Vec<String>,
+    /// Condition over earlier inputs (`cloud == 'aws'` or a template); the
+    /// input is neither asked for nor required when it is false.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<String>,
+    /// Constraints checked after type coercion.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub validation: Vec<ValidationRule>,
+}
+
+/// Constraints on an input value. All constraints set on one rule must hold;
+/// `message` replaces the generated error when one fails.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ValidationRule {
+    /// Regex a string must match (use `^...$` to match the whole value).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    /// Smallest allowed number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// Largest allowed number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Minimum length of a string (characters), list or map.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    /// Maximum length of a string (characters), list or map.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    /// Allowed values.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<Vec<serde_json::Value>>")]
+    pub one_of: Option<Vec<serde_yml::Value>>,
+    /// Error shown when the rule fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A task wrapping a native command.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TaskDef {
+    /// Command line run in the app folder.
     pub command: String,
+    /// Help text for the task.
     pub description: Option<String>,
 }
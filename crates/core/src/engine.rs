@@ -15,7 +15,7 @@ impl Engine {
         context: &tera::Context,
         dest: &Path,
     ) -> anyhow::Result<()> {
-        let content = template_engine.render(template_str, context)?;
+        let content = template_engine.render(&dest.to_string_lossy(), template_str, context)?;
         if let Some(parent) = dest.parent() {
             ensure_dir(parent)?;
         }
@@ -0,0 +1,440 @@
+//! Filters available to every module template, on top of Tera's built-ins.
+//!
+//! * `snake_case`, `kebab_case`, `pascal_case`: change the case of a string.
+//! * `to_yaml`, `to_json(pretty=false)`, `to_toml`, `to_hcl`: serialize a value.
+//! * `indent(width=4, prefix, first=false, blank=false)`: indent every line
+//!   but the first (Tera's `indent`, plus `width`).
+//! * `sha256`: hex digest of a string; `base64`: standard base64 of a string.
+//! * `semver_bump(part="patch")`: next `major`, `minor` or `patch` version.
+//! * `relpath(from)`: the path relative to the folder `from`, e.g. from one
+//!   app path to another.
+//! * `uuid5(namespace="url")`: name-based UUID of a string, in the `dns`,
+//!   `url`, `oid` or `x500` namespace or under a given UUID.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use heck::{ToKebabCase, ToPascalCase, ToSnakeCase};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use tera::{Error, Result, Tera, Value};
+
+/// Registers the filters on `tera`.
+pub fn register(tera: &mut Tera) {
+    tera.register_filter("snake_case", snake_case);
+    tera.register_filter("kebab_case", kebab_case);
+    tera.register_filter("pascal_case", pascal_case);
+    tera.register_filter("to_yaml", to_yaml);
+    tera.register_filter("to_json", to_json);
+    tera.register_filter("to_toml", to_toml);
+    tera.register_filter("to_hcl", to_hcl);
+    tera.register_filter("indent", indent);
+    tera.register_filter("sha256", sha256);
+    tera.register_filter("base64", base64);
+    tera.register_filter("semver_bump", semver_bump);
+    tera.register_filter("relpath", relpath);
+    tera.register_filter("uuid5", uuid5);
+}
+
+type Args = HashMap<String, Value>;
+
+fn string<'a>(filter: &str, value: &'a Value) -> Result<&'a str> {
+    value.as_str().ok_or_else(|| {
+        Error::msg(format!(
+            "filter `{}` expects a string, got {}",
+            filter, value
+        ))
+    })
+}
+
+fn string_arg<'a>(filter: &str, args: &'a Args, name: &str) -> Result<Option<&'a str>> {
+    match args.get(name) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(other) => Err(Error::msg(format!(
+            "filter `{}`: argument `{}` must be a string, got {}",
+            filter, name, other
+        ))),
+    }
+}
+
+fn bool_arg(filter: &str, args: &Args, name: &str) -> Result<bool> {
+    match args.get(name) {
+        None => Ok(false),
+        Some(Value::Bool(b)) => Ok(*b),
+        Some(other) => Err(Error::msg(format!(
+            "filter `{}`: argument `{}` must be a boolean, got {}",
+            filter, name, other
+        ))),
+    }
+}
+
+fn snake_case(value: &Value, _: &Args) -> Result<Value> {
+    Ok(string("snake_case", value)?.to_snake_case().into())
+}
+
+fn kebab_case(value: &Value, _: &Args) -> Result<Value> {
+    Ok(string("kebab_case", value)?.to_kebab_case().into())
+}
+
+fn pascal_case(value: &Value, _: &Args) -> Result<Value> {
+    Ok(string("pascal_case", value)?.to_pascal_case().into())
+}
+
+fn to_yaml(value: &Value, _: &Args) -> Result<Value> {
+    let yaml = serde_yml::to_string(value).map_err(|e| Error::msg(format!("to_yaml: {}", e)))?;
+    Ok(yaml.trim_end().into())
+}
+
+fn to_json(value: &Value, args: &Args) -> Result<Value> {
+    let json = if bool_arg("to_json", args, "pretty")? {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    Ok(json
+        .map_err(|e| Error::msg(format!("to_json: {}", e)))?
+        .into())
+}
+
+fn to_toml(value: &Value, _: &Args) -> Result<Value> {
+    if !value.is_object() {
+        return Err(Error::msg(format!(
+            "filter `to_toml` expects a map, got {}",
+            value
+        )));
+    }
+    let toml = toml::to_string(value).map_err(|e| Error::msg(format!("to_toml: {}", e)))?;
+    Ok(toml.trim_end().into())
+}
+
+/// A map becomes one `key = value` attribute per line, anything else a
+/// single HCL expression.
+fn to_hcl(value: &Value, _: &Args) -> Result<Value> {
+    let hcl = match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| format!("{} = {}", hcl_key(key), hcl_expression(value, 0)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        other => hcl_expression(other, 0),
+    };
+    Ok(hcl.into())
+}
+
+fn hcl_key(key: &str) -> String {
+    let identifier = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if identifier {
+        key.to_string()
+    } else {
+        hcl_string(key)
+    }
+}
+
+fn hcl_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    // `${` and `%{` would start an interpolation or a directive
+    quoted.replace("${", "$${").replace("%{", "%%{")
+}
+
+fn hcl_expression(value: &Value, depth: usize) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => hcl_string(s),
+        Value::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .map(|item| hcl_expression(item, depth))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Object(map) if map.is_empty() => "{}".to_string(),
+        Value::Object(map) => {
+            let pad = "  ".repeat(depth + 1);
+            let attributes = map
+                .iter()
+                .map(|(key, value)| {
+                    format!(
+                        "{}{} = {}",
+                        pad,
+                        hcl_key(key),
+                        hcl_expression(value, depth + 1)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{{\n{}\n{}}}", attributes, "  ".repeat(depth))
+        }
+    }
+}
+
+fn indent(value: &Value, args: &Args) -> Result<Value> {
+    let text = string("indent", value)?;
+    let prefix = match (string_arg("indent", args, "prefix")?, args.get("width")) {
+        (Some(prefix), _) => prefix.to_string(),
+        (None, Some(width)) => {
+            let width = width.as_u64().ok_or_else(|| {
+                Error::msg(format!(
+                    "filter `indent`: argument `width` must be a number, got {}",
+                    width
+                ))
+            })?;
+            " ".repeat(width as usize)
+        }
+        (None, None) => " ".repeat(4),
+    };
+    let first = bool_arg("indent", args, "first")?;
+    let blank = bool_arg("indent", args, "blank")?;
+
+    let mut indented = String::with_capacity(text.len());
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let is_blank = line.trim_end_matches(['\r', '\n']).is_empty();
+        if (i > 0 || first) && (blank || !is_blank) {
+            indented.push_str(&prefix);
+        }
+        indented.push_str(line);
+    }
+    Ok(indented.into())
+}
+
+fn sha256(value: &Value, _: &Args) -> Result<Value> {
+    let digest = Sha256::digest(string("sha256", value)?.as_bytes());
+    Ok(digest
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+        .into())
+}
+
+fn base64(value: &Value, _: &Args) -> Result<Value> {
+    Ok(BASE64.encode(string("base64", value)?).into())
+}
+
+fn semver_bump(value: &Value, args: &Args) -> Result<Value> {
+    let text = string("semver_bump", value)?;
+    // A leading `v` (tags) is kept
+    let (prefix, text) = match text.strip_prefix('v') {
+        Some(rest) => ("v", rest),
+        None => ("", text),
+    };
+    let mut version = semver::Version::parse(text)
+        .map_err(|e| Error::msg(format!("semver_bump: '{}': {}", text, e)))?;
+    // A prerelease comes before its release, so bumping `1.4.2-rc.1` releases
+    // `1.4.2` rather than skipping it; likewise `2.0.0-rc.1` to major `2.0.0`
+    let pre = !version.pre.is_empty();
+    match string_arg("semver_bump", args, "part")?.unwrap_or("patch") {
+        "major" if pre && version.minor == 0 && version.patch == 0 => {}
+        "major" => {
+            version.major += 1;
+            version.minor = 0;
+            version.patch = 0;
+        }
+        "minor" if pre && version.patch == 0 => {}
+        "minor" => {
+            version.minor += 1;
+            version.patch = 0;
+        }
+        "patch" if pre => {}
+        "patch" => version.patch += 1,
+        other => {
+            return Err(Error::msg(format!(
+                "semver_bump: part must be major, minor or patch, got '{}'",
+                other
+            )));
+        }
+    }
+    version.pre = semver::Prerelease::EMPTY;
+    version.build = semver::BuildMetadata::EMPTY;
+    Ok(format!("{}{}", prefix, version).into())
+}
+
+fn relpath(value: &Value, args: &Args) -> Result<Value> {
+    let to = string("relpath", value)?;
+    let from = string_arg("relpath", args, "from")?
+        .ok_or_else(|| Error::msg("filter `relpath` expects a `from` argument"))?;
+    relative_path(Path::new(from), Path::new(to))
+        .map(Value::from)
+        .map_err(|e| Error::msg(format!("relpath: {}", e)))
+}
+
+/// `to` relative to the folder `from`, both relative to the same root.
+/// Fails when either leaves the root, as the folders above it are unknown.
+fn relative_path(from: &Path, to: &Path) -> std::result::Result<String, String> {
+    fn parts(path: &Path) -> std::result::Result<Vec<String>, String> {
+        let mut parts: Vec<String> = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+                Component::ParentDir => {
+                    parts
+                        .pop()
+                        .ok_or_else(|| format!("'{}' is outside the root", path.display()))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(parts)
+    }
+    let from = parts(from)?;
+    let to = parts(to)?;
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let relative: Vec<&str> = std::iter::repeat_n("..", from.len() - common)
+        .chain(to[common..].iter().map(String::as_str))
+        .collect();
+    if relative.is_empty() {
+        Ok(".".to_string())
+    } else {
+        Ok(relative.join("/"))
+    }
+}
+
+fn uuid5(value: &Value, args: &Args) -> Result<Value> {
+    let name = string("uuid5", value)?;
+    let namespace = match string_arg("uuid5", args, "namespace")?.unwrap_or("url") {
+        "dns" => uuid::Uuid::NAMESPACE_DNS,
+        "url" => uuid::Uuid::NAMESPACE_URL,
+        "oid" => uuid::Uuid::NAMESPACE_OID,
+        "x500" => uuid::Uuid::NAMESPACE_X500,
+        other => uuid::Uuid::parse_str(other).map_err(|_| {
+            Error::msg(format!(
+                "uuid5: namespace must be dns, url, oid, x500 or a UUID, got '{}'",
+                other
+            ))
+        })?,
+    };
+    Ok(uuid::Uuid::new_v5(&namespace, name.as_bytes())
+        .to_string()
+        .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tera::Context;
+
+    fn render(template: &str, context: &Context) -> String {
+        let mut tera = Tera::default();
+        register(&mut tera);
+        tera.render_str(template, context).unwrap()
+    }
+
+    #[test]
+    fn test_case_and_encoding_filters() {
+        let mut context = Context::new();
+        context.insert("name", "my-App name");
+        assert_eq!(
+            render(
+                "{{ name | snake_case }} {{ name | kebab_case }} {{ name | pascal_case }}",
+                &context
+            ),
+            "my_app_name my-app-name MyAppName"
+        );
+        assert_eq!(
+            render("{{ 'abc' | sha256 }}", &context),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            render("{{ 'rw:token' | base64 }}", &context),
+            "cnc6dG9rZW4="
+        );
+        assert_eq!(
+            render("{{ 'python.org' | uuid5(namespace='dns') }}", &context),
+            "886313e1-3b8a-5372-9b90-0c9aee199e5d"
+        );
+    }
+
+    #[test]
+    fn test_serialization_filters() {
+        let mut context = Context::new();
+        context.insert(
+            "vars",
+            &serde_json::json!({ "region": "eu-west-1", "tags": ["a", "b"], "sizes": { "min": 1 } }),
+        );
+        assert_eq!(
+            render("{{ vars | to_json }}", &context),
+            r#"{"region":"eu-west-1","sizes":{"min":1},"tags":["a","b"]}"#
+        );
+        assert_eq!(
+            render("{{ vars | to_yaml }}", &context),
+            "region: eu-west-1\nsizes:\n  min: 1\ntags:\n- a\n- b"
+        );
+        assert_eq!(
+            render("{{ vars | to_toml }}", &context),
+            "region = \"eu-west-1\"\ntags = [\"a\", \"b\"]\n\n[sizes]\nmin = 1"
+        );
+        assert_eq!(
+            render("{{ vars | to_hcl }}", &context),
+            "region = \"eu-west-1\"\nsizes = {\n  min = 1\n}\ntags = [\"a\", \"b\"]"
+        );
+        assert_eq!(render("{{ '${var}' | to_hcl }}", &context), "\"$${var}\"");
+    }
+
+    #[test]
+    fn test_text_and_version_filters() {
+        let mut context = Context::new();
+        context.insert("block", "a:\n  b: 1\n\nc: 2");
+        assert_eq!(
+            render("x:\n  {{ block | indent(width=2) }}", &context),
+            "x:\n  a:\n    b: 1\n\n  c: 2"
+        );
+        assert_eq!(
+            render(
+                "{{ '1.4.2' | semver_bump }} {{ 'v1.4.2' | semver_bump(part='minor') }} {{ '1.4.2-rc.1' | semver_bump(part='major') }}",
+                &context
+            ),
+            "1.4.3 v1.5.0 2.0.0"
+        );
+        // Bumping a prerelease releases it when it is already of that part
+        assert_eq!(
+            render(
+                "{{ '1.4.2-rc.1' | semver_bump }} {{ '1.5.0-rc.1' | semver_bump(part='minor') }} {{ '1.4.2-rc.1' | semver_bump(part='minor') }} {{ 'v2.0.0-beta' | semver_bump(part='major') }}",
+                &context
+            ),
+            "1.4.2 1.5.0 1.5.0 v2.0.0"
+        );
+        assert_eq!(
+            render(
+                "{{ 'infra/network' | relpath(from='apps/api') }} {{ 'apps/api/db' | relpath(from='apps/api') }} {{ 'apps' | relpath(from='apps') }}",
+                &context
+            ),
+            "../../infra/network db ."
+        );
+        assert_eq!(
+            render("{{ 'a/../b' | relpath(from='c/./d/..') }}", &context),
+            "../b"
+        );
+        for template in [
+            "{{ 'infra' | relpath(from='../x') }}",
+            "{{ '../x' | relpath(from='infra') }}",
+        ] {
+            let mut tera = Tera::default();
+            register(&mut tera);
+            let err = tera.render_str(template, &context).unwrap_err();
+            let detail = std::iter::successors(std::error::Error::source(&err), |e| e.source())
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join(": ");
+            assert!(detail.contains("'../x' is outside the root"), "{detail}");
+        }
+    }
+}
@@ -0,0 +1,771 @@
+//! Types of module inputs, the coercion of values into them and the
+//! `validation` rules checked afterwards, plus the `when` conditions and
+//! templated defaults that make inputs depend on each other, and inputs
+//! derived from the output of a command.
+
+use crate::config::{InputDef, ValidationRule};
+use crate::template;
+use indexmap::IndexMap;
+use regex::Regex;
+use serde_yml::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+use std::sync::LazyLock;
+
+/// Type and validation rules of one input, parsed from its [`InputDef`].
+#[derive(Debug, Clone)]
+pub struct InputSpec {
+    pub ty: InputType,
+    rules: Vec<(ValidationRule, Option<Regex>)>,
+}
+
+impl InputSpec {
+    pub fn of(def: &InputDef) -> anyhow::Result<Self> {
+        let ty = InputType::of(def)?;
+        let mut rules = Vec::new();
+        for (idx, rule) in def.validation.iter().enumerate() {
+            let applies = |what: &str, ok: bool| {
+                if ok {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!(
+                        "validation rule {}: '{}' does not apply to type '{}'",
+                        idx + 1,
+                        what,
+                        ty
+                    ))
+                }
+            };
+            let numeric = matches!(ty, InputType::Int | InputType::Float);
+            let sized = matches!(ty, InputType::String | InputType::List(_) | InputType::Map);
+            if rule.min.is_some() {
+                applies("min", numeric)?;
+            }
+            if rule.max.is_some() {
+                applies("max", numeric)?;
+            }
+            if rule.min_length.is_some() {
+                applies("min_length", sized)?;
+            }
+            if rule.max_length.is_some() {
+                applies("max_length", sized)?;
+            }
+            let regex = match &rule.pattern {
+                Some(pattern) => {
+                    applies("pattern", matches!(ty, InputType::String))?;
+                    Some(Regex::new(pattern).map_err(|e| {
+                        anyhow::anyhow!("validation rule {}: invalid pattern: {}", idx + 1, e)
+                    })?)
+                }
+                None => None,
+            };
+            rules.push((rule.clone(), regex));
+        }
+        Ok(Self { ty, rules })
+    }
+
+    /// Coerces `value` to the input type and checks the validation rules.
+    pub fn check(&self, value: &Value) -> anyhow::Result<Value> {
+        let value = self.ty.coerce(value)?;
+        for (rule, regex) in &self.rules {
+            if let Some(problem) = rule_violation(rule, regex.as_ref(), &value) {
+                anyhow::bail!("{}", rule.message.clone().unwrap_or(problem));
+            }
+        }
+        Ok(value)
+    }
+
+    /// Converts and checks text typed at a prompt.
+    pub fn parse_text(&self, text: &str) -> anyhow::Result<Value> {
+        let value = self.ty.parse_text(text)?;
+        self.check(&value)
+    }
+}
+
+/// The first constraint of `rule` that `value` (already coerced) breaks.
+fn rule_violation(rule: &ValidationRule, regex: Option<&Regex>, value: &Value) -> Option<String> {
+    if let (Some(regex), Value::String(s)) = (regex, value)
+        && !regex.is_match(s)
+    {
+        return Some(format!("must match pattern '{}'", regex.as_str()));
+    }
+    if let Some(n) = value.as_f64() {
+        if let Some(min) = rule.min
+            && n < min
+        {
+            return Some(format!("must be at least {}", min));
+        }
+        if let Some(max) = rule.max
+            && n > max
+        {
+            return Some(format!("must be at most {}", max));
+        }
+    }
+    let (length, unit) = match value {
+        Value::String(s) => (Some(s.chars().count()), "characters"),
+        Value::Sequence(items) => (Some(items.len()), "items"),
+        Value::Mapping(map) => (Some(map.len()), "entries"),
+        _ => (None, ""),
+    };
+    if let Some(length) = length {
+        if let Some(min) = rule.min_length
+            && length < min
+        {
+            return Some(format!("must have at least {} {}", min, unit));
+        }
+        if let Some(max) = rule.max_length
+            && length > max
+        {
+            return Some(format!("must have at most {} {}", max, unit));
+        }
+    }
+    if let Some(allowed) = &rule.one_of
+        && !allowed.contains(value)
+    {
+        return Some(format!(
+            "must be one of {}",
+            allowed.iter().map(describe).collect::<Vec<_>>().join(", ")
+        ));
+    }
+    None
+}
+
+/// Parsed `type` of an [`InputDef`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputType {
+    String,
+    Int,
+    Float,
+    Bool,
+    /// `list`, `list<T>` or `list(T)`; `None` accepts items of any type
+    List(Option<Box<InputType>>),
+    Map,
+    /// `enum` (or items of `list<enum>`), restricted to the input's `values`
+    Enum(Vec<String>),
+}
+
+impl InputType {
+    /// Parses the `type` and, for enums, `values` of `def`.
+    pub fn of(def: &InputDef) -> anyhow::Result<Self> {
+        let ty = Self::parse(&def.r#type)?;
+        let enum_slot = match &ty {
+            InputType::Enum(_) => true,
+            InputType::List(Some(item)) => matches!(**item, InputType::Enum(_)),
+            _ => false,
+        };
+        match (enum_slot, def.values.is_empty()) {
+            (true, true) => anyhow::bail!("type '{}' needs a non-empty 'values' list", ty),
+            (false, false) => {
+                anyhow::bail!("'values' is only allowed for types 'enum' and 'list<enum>'")
+            }
+            (true, false) => Ok(match ty {
+                InputType::List(_) => {
+                    InputType::List(Some(Box::new(InputType::Enum(def.values.clone()))))
+                }
+                _ => InputType::Enum(def.values.clone()),
+            }),
+            (false, true) => Ok(ty),
+        }
+    }
+
+    fn parse(raw: &str) -> anyhow::Result<Self> {
+        let raw = raw.trim();
+        let inner = raw
+            .strip_prefix("list<")
+            .and_then(|s| s.strip_suffix('>'))
+            .or_else(|| raw.strip_prefix("list(").and_then(|s| s.strip_suffix(')')));
+        if let Some(inner) = inner {
+            return Ok(InputType::List(Some(Box::new(Self::parse(inner)?))));
+        }
+        Ok(match raw {
+            "string" => InputType::String,
+            "int" | "integer" => InputType::Int,
+            "float" | "number" => InputType::Float,
+            "bool" | "boolean" => InputType::Bool,
+            "list" => InputType::List(None),
+            "map" => InputType::Map,
+            "enum" => InputType::Enum(Vec::new()),
+            other => anyhow::bail!(
+                "unknown type '{}' (expected string, int, float, bool, list<T>, map or enum)",
+                other
+            ),
+        })
+    }
+
+    /// Checks `value` against the type, converting it where that is lossless
+    /// (e.g. `"3"` for an int, `3` for a string).
+    pub fn coerce(&self, value: &Value) -> anyhow::Result<Value> {
+        let mismatch = || anyhow::anyhow!("expected {}, got {}", self, describe(value));
+        match (self, value) {
+            (InputType::String, Value::String(_)) => Ok(value.clone()),
+            (InputType::String, Value::Number(n)) => Ok(Value::String(n.to_string())),
+            (InputType::String, Value::Bool(b)) => Ok(Value::String(b.to_string())),
+
+            (InputType::Int, Value::Number(n)) => match n.as_i64() {
+                Some(i) => Ok(Value::from(i)),
+                None => match n.as_f64() {
+                    Some(f) if f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+                        Ok(Value::from(f as i64))
+                    }
+                    _ => Err(mismatch()),
+                },
+            },
+            (InputType::Int, Value::String(s)) => s
+                .trim()
+                .parse::<i64>()
+                .map(Value::from)
+                .map_err(|_| mismatch()),
+
+            (InputType::Float, Value::Number(_)) => Ok(value.clone()),
+            (InputType::Float, Value::String(s)) => match s.trim().parse::<i64>() {
+                Ok(i) => Ok(Value::from(i)),
+                Err(_) => s
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|f| f.is_finite())
+                    .map(Value::from)
+                    .ok_or_else(mismatch),
+            },
+
+            (InputType::Bool, Value::Bool(_)) => Ok(value.clone()),
+            (InputType::Bool, Value::String(s)) => match s.trim().to_lowercase().as_str() {
+                "true" | "yes" => Ok(Value::Bool(true)),
+                "false" | "no" => Ok(Value::Bool(false)),
+                _ => Err(mismatch()),
+            },
+
+            (InputType::List(None), Value::Sequence(_)) => Ok(value.clone()),
+            (InputType::List(Some(item)), Value::Sequence(items)) => items
+                .iter()
+                .enumerate()
+                .map(|(idx, v)| {
+                    item.coerce(v)
+                        .map_err(|e| anyhow::anyhow!("item {}: {}", idx, e))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map(Value::Sequence),
+
+            (InputType::Map, Value::Mapping(_)) => Ok(value.clone()),
+
+            (InputType::Enum(values), Value::String(_) | Value::Number(_) | Value::Bool(_)) => {
+                let text = scalar_text(value);
+                if values.contains(&text) {
+                    Ok(Value::String(text))
+                } else {
+                    Err(anyhow::anyhow!(
+                        "expected one of {}, got {}",
+                        values
+                            .iter()
+                            .map(|v| format!("'{}'", v))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        describe(value)
+                    ))
+                }
+            }
+
+            _ => Err(mismatch()),
+        }
+    }
+
+    /// Converts text typed at a prompt. Lists are comma-separated and maps
+    /// are written as YAML flow mappings (`{a: 1}`).
+    pub fn parse_text(&self, text: &str) -> anyhow::Result<Value> {
+        match self {
+            InputType::List(item) => {
+                if text.trim().is_empty() {
+                    return Ok(Value::Sequence(Vec::new()));
+                }
+                let items = text
+                    .split(',')
+                    .map(|part| Value::String(part.trim().to_string()))
+                    .collect();
+                match item {
+                    Some(_) => self.coerce(&Value::Sequence(items)),
+                    None => Ok(Value::Sequence(items)),
+                }
+            }
+            InputType::Map => {
+                let value: Value = serde_yml::from_str(text)
+                    .map_err(|_| anyhow::anyhow!("expected a map such as {{key: value}}"))?;
+                self.coerce(&value)
+            }
+            _ => self.coerce(&Value::String(text.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for InputType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputType::String => write!(f, "string"),
+            InputType::Int => write!(f, "int"),
+            InputType::Float => write!(f, "float"),
+            InputType::Bool => write!(f, "bool"),
+            InputType::List(None) => write!(f, "list"),
+            InputType::List(Some(item)) => write!(f, "list<{}>", item),
+            InputType::Map => write!(f, "map"),
+            InputType::Enum(_) => write!(f, "enum"),
+        }
+    }
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::String(s) => format!("\"{}\"", s),
+        Value::Number(_) | Value::Bool(_) => scalar_text(value),
+        Value::Sequence(_) => "a list".to_string(),
+        Value::Mapping(_) => "a map".to_string(),
+        Value::Tagged(_) => "a tagged value".to_string(),
+    }
+}
+
+/// Whether `text` is a template (`{{ ... }}` or `{% ... %}`) rather than a
+/// literal value.
+pub fn is_template(text: &str) -> bool {
+    text.contains("{{") || text.contains("{%")
+}
+
+/// Inputs named in the templates of `def` (its `when` and templated default).
+fn dependencies<'a>(def: &InputDef, names: &[&'a str]) -> Vec<&'a str> {
+    static BLOCK: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?s)\{\{(.*?)\}\}|\{%(.*?)%\}").unwrap());
+    static STRING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"'[^']*'|"[^"]*""#).unwrap());
+    static IDENT: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(^|[^.\w])([A-Za-z_]\w*)").unwrap());
+
+    let mut sources = Vec::new();
+    if let Some(when) = &def.when {
+        sources.push(when_template(when));
+    }
+    if let Some(Value::String(default)) = &def.default
+        && is_template(default)
+    {
+        sources.push(default.clone());
+    }
+
+    let mut found = Vec::new();
+    for source in &sources {
+        for block in BLOCK.captures_iter(source) {
+            let code = block.get(1).or(block.get(2)).map_or("", |m| m.as_str());
+            let code = STRING.replace_all(code, "''");
+            for ident in IDENT.captures_iter(&code) {
+                let ident = &ident[2];
+                if let Some(name) = names.iter().find(|n| **n == ident)
+                    && !found.contains(name)
+                {
+                    found.push(*name);
+                }
+            }
+        }
+    }
+    found
+}
+
+/// `when` may be a bare expression (`cloud == 'aws'`) or a template.
+pub(crate) fn when_template(when: &str) -> String {
+    if is_template(when) {
+        when.to_string()
+    } else {
+        format!("{{{{ {} }}}}", when)
+    }
+}
+
+/// Orders inputs so that each comes after the inputs its `when` and default
+/// refer to, otherwise keeping declaration order. Fails on circular references.
+pub fn resolution_order(inputs: &IndexMap<String, InputDef>) -> anyhow::Result<Vec<String>> {
+    fn visit<'a>(
+        name: &'a str,
+        deps: &HashMap<&'a str, Vec<&'a str>>,
+        done: &mut HashSet<&'a str>,
+        path: &mut Vec<&'a str>,
+        order: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|n| *n == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name);
+            anyhow::bail!("circular reference between inputs: {}", cycle.join(" -> "));
+        }
+        path.push(name);
+        for dep in &deps[name] {
+            visit(dep, deps, done, path, order)?;
+        }
+        path.pop();
+        done.insert(name);
+        order.push(name.to_string());
+        Ok(())
+    }
+
+    let names: Vec<&str> = inputs.keys().map(String::as_str).collect();
+    let deps: HashMap<&str, Vec<&str>> = inputs
+        .iter()
+        .map(|(name, def)| (name.as_str(), dependencies(def, &names)))
+        .collect();
+
+    let mut order = Vec::new();
+    let mut done = HashSet::new();
+    for name in &names {
+        visit(name, &deps, &mut done, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+fn render(template: &str, context: &HashMap<String, Value>) -> anyhow::Result<String> {
+    let context = tera::Context::from_serialize(context)?;
+    // Same filters and escaping as module templates
+    template::new_tera()
+        .render_str(template, &context)
+        .map_err(|e| {
+            // Tera keeps the useful part (e.g. an unknown variable) in the source
+            let detail = std::error::Error::source(&e)
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| e.to_string());
+            anyhow::anyhow!("{}", detail)
+        })
+}
+
+/// Evaluates the `when` condition of `def` against the inputs resolved so
+/// far. Inputs without a condition are always active.
+pub fn is_active(def: &InputDef, context: &HashMap<String, Value>) -> anyhow::Result<bool> {
+    let Some(when) = &def.when else {
+        return Ok(true);
+    };
+    let rendered = render(&when_template(when), context)
+        .map_err(|e| anyhow::anyhow!("'when' failed: {}", e))?;
+    when_result(&rendered)
+}
+
+/// The outcome of a rendered `when` condition.
+pub(crate) fn when_result(rendered: &str) -> anyhow::Result<bool> {
+    match rendered.trim() {
+        "true" => Ok(true),
+        "false" | "" => Ok(false),
+        other => anyhow::bail!("'when' must evaluate to true or false, got '{}'", other),
+    }
+}
+
+/// The default of `def`, rendered against the inputs resolved so far when
+/// it is a template. Returns whether it was computed, as computed defaults
+/// are recorded in the answers file.
+pub fn default_value(
+    def: &InputDef,
+    context: &HashMap<String, Value>,
+) -> anyhow::Result<Option<(Value, bool)>> {
+    match &def.default {
+        Some(Value::String(template)) if is_template(template) => {
+            let rendered =
+                render(template, context).map_err(|e| anyhow::anyhow!("default failed: {}", e))?;
+            Ok(Some((Value::String(rendered), true)))
+        }
+        Some(value) => Ok(Some((value.clone(), false))),
+        None => Ok(None),
+    }
+}
+
+/// Checks the `from_command` settings of `def`.
+pub fn check_derivation(def: &InputDef) -> anyhow::Result<()> {
+    if def.from_command.is_empty() {
+        if def.json_pointer.is_some() || def.regex.is_some() {
+            anyhow::bail!("'json_pointer' and 'regex' need 'from_command'");
+        }
+        return Ok(());
+    }
+    if def.json_pointer.is_some() && def.regex.is_some() {
+        anyhow::bail!("set either 'json_pointer' or 'regex', not both");
+    }
+    if def.default.is_some() {
+        anyhow::bail!("'from_command' and 'default' are exclusive");
+    }
+    if let Some(pointer) = &def.json_pointer
+        && !pointer.is_empty()
+        && !pointer.starts_with('/')
+    {
+        anyhow::bail!(
+            "'json_pointer' must be empty or start with '/', got '{}'",
+            pointer
+        );
+    }
+    if let Some(regex) = &def.regex {
+        Regex::new(regex).map_err(|e| anyhow::anyhow!("invalid 'regex': {}", e))?;
+    }
+    Ok(())
+}
+
+/// Runs the `from_command` of `def` in `dir` and extracts the input value
+/// from its output. `None` when the input is not derived.
+pub fn derived_value(def: &InputDef, dir: &Path) -> anyhow::Result<Option<Value>> {
+    let Some((program, args)) = def.from_command.split_first() else {
+        return Ok(None);
+    };
+    let command = def.from_command.join(" ");
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| anyhow::anyhow!("command `{}` could not run: {}", command, e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "command `{}` failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    if let Some(pointer) = &def.json_pointer {
+        let json: serde_json::Value = serde_json::from_str(&stdout)
+            .map_err(|e| anyhow::anyhow!("command `{}` did not print JSON: {}", command, e))?;
+        let selected = json.pointer(pointer).ok_or_else(|| {
+            anyhow::anyhow!("command `{}`: JSON has nothing at '{}'", command, pointer)
+        })?;
+        return Ok(Some(serde_yml::to_value(selected)?));
+    }
+    if let Some(regex) = &def.regex {
+        let regex = Regex::new(regex)?;
+        let captures = regex.captures(&stdout).ok_or_else(|| {
+            anyhow::anyhow!(
+                "command `{}`: output does not match '{}'",
+                command,
+                regex.as_str()
+            )
+        })?;
+        let matched = captures
+            .get(1)
+            .or(captures.get(0))
+            .map_or("", |m| m.as_str());
+        return Ok(Some(Value::String(matched.to_string())));
+    }
+    Ok(Some(Value::String(stdout.trim().to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ty(raw: &str) -> InputType {
+        InputType::parse(raw).unwrap()
+    }
+
+    #[test]
+    fn test_parse_types() {
+        assert_eq!(
+            ty("list<int>"),
+            InputType::List(Some(Box::new(InputType::Int)))
+        );
+        assert_eq!(
+            ty("list(string)"),
+            InputType::List(Some(Box::new(InputType::String)))
+        );
+        assert_eq!(ty("number"), InputType::Float);
+        assert!(InputType::parse("strnig").is_err());
+    }
+
+    #[test]
+    fn test_coercion() {
+        assert_eq!(ty("int").coerce(&Value::from("3")).unwrap(), Value::from(3));
+        assert_eq!(
+            ty("bool").coerce(&Value::from("false")).unwrap(),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            ty("string").coerce(&Value::from(3)).unwrap(),
+            Value::from("3")
+        );
+        assert_eq!(
+            ty("list<int>")
+                .coerce(&serde_yml::from_str("[1, \"2\"]").unwrap())
+                .unwrap(),
+            serde_yml::from_str::<Value>("[1, 2]").unwrap()
+        );
+
+        let err = ty("int").coerce(&Value::from("three")).unwrap_err();
+        assert_eq!(err.to_string(), "expected int, got \"three\"");
+        assert!(ty("bool").coerce(&Value::from(1)).is_err());
+        assert!(ty("map").coerce(&Value::from("a")).is_err());
+
+        let colors = InputType::Enum(vec!["red".into(), "blue".into()]);
+        assert!(colors.coerce(&Value::from("red")).is_ok());
+        assert!(colors.coerce(&Value::from("green")).is_err());
+    }
+
+    #[test]
+    fn test_enum_values() {
+        let def: InputDef =
+            serde_yml::from_str("{ type: \"list<enum>\", values: [a, b] }").unwrap();
+        assert_eq!(
+            InputType::of(&def).unwrap(),
+            InputType::List(Some(Box::new(InputType::Enum(vec![
+                "a".into(),
+                "b".into()
+            ]))))
+        );
+
+        let def: InputDef = serde_yml::from_str("{ type: enum }").unwrap();
+        assert!(InputType::of(&def).is_err());
+        let def: InputDef = serde_yml::from_str("{ type: int, values: [1] }").unwrap();
+        assert!(InputType::of(&def).is_err());
+    }
+
+    #[test]
+    fn test_validation_rules() {
+        let def: InputDef = serde_yml::from_str(
+            r#"
+type: int
+validation:
+  - { min: 1024, max: 65535, message: "use an unprivileged port" }
+  - { one_of: [8080, 8443] }
+"#,
+        )
+        .unwrap();
+        let spec = InputSpec::of(&def).unwrap();
+        assert_eq!(spec.check(&Value::from("8080")).unwrap(), Value::from(8080));
+        assert_eq!(
+            spec.check(&Value::from(80)).unwrap_err().to_string(),
+            "use an unprivileged port"
+        );
+        assert_eq!(
+            spec.check(&Value::from(9000)).unwrap_err().to_string(),
+            "must be one of 8080, 8443"
+        );
+
+        let def: InputDef = serde_yml::from_str(
+            "{ type: string, validation: [{ pattern: \"^[a-z0-9-]+$\", max_length: 8 }] }",
+        )
+        .unwrap();
+        let spec = InputSpec::of(&def).unwrap();
+        assert!(spec.check(&Value::from("my-bucket")).is_err());
+        assert!(spec.check(&Value::from("My_B")).is_err());
+        assert!(spec.check(&Value::from("bucket")).is_ok());
+
+        let def: InputDef =
+            serde_yml::from_str("{ type: bool, validation: [{ min: 1 }] }").unwrap();
+        assert!(InputSpec::of(&def).is_err());
+    }
+
+    fn manifest_inputs(yaml: &str) -> IndexMap<String, InputDef> {
+        serde_yml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_resolution_order_follows_references() {
+        let inputs = manifest_inputs(
+            r#"
+bucket: { type: string, default: "{{ name }}-state-bucket", when: "cloud == 'aws'" }
+cloud: { type: enum, values: [aws, gcp] }
+name: { type: string }
+"#,
+        );
+        assert_eq!(
+            resolution_order(&inputs).unwrap(),
+            vec!["cloud", "name", "bucket"]
+        );
+
+        let context: HashMap<String, Value> = [
+            ("cloud".to_string(), Value::from("aws")),
+            ("name".to_string(), Value::from("billing")),
+        ]
+        .into();
+        let bucket = &inputs["bucket"];
+        assert!(is_active(bucket, &context).unwrap());
+        assert_eq!(
+            default_value(bucket, &context).unwrap(),
+            Some((Value::from("billing-state-bucket"), true))
+        );
+
+        let gcp: HashMap<String, Value> = [("cloud".to_string(), Value::from("gcp"))].into();
+        assert!(!is_active(bucket, &gcp).unwrap());
+    }
+
+    #[test]
+    fn test_defaults_and_conditions_use_template_filters() {
+        let inputs = manifest_inputs(
+            r#"
+slug: { type: string, default: "{{ name | kebab_case }} & co" }
+snake: { type: string, when: "name | snake_case == 'billing_api'" }
+"#,
+        );
+        let context: HashMap<String, Value> =
+            [("name".to_string(), Value::from("BillingApi"))].into();
+        assert_eq!(
+            default_value(&inputs["slug"], &context).unwrap(),
+            Some((Value::from("billing-api & co"), true))
+        );
+        assert!(is_active(&inputs["snake"], &context).unwrap());
+    }
+
+    #[test]
+    fn test_circular_references() {
+        let inputs = manifest_inputs(
+            r#"
+a: { type: string, default: "{{ b }}" }
+b: { type: string, when: "{{ a == 'x' }}" }
+"#,
+        );
+        let err = resolution_order(&inputs).unwrap_err().to_string();
+        assert_eq!(err, "circular reference between inputs: a -> b -> a");
+    }
+
+    #[test]
+    fn test_parse_text() {
+        assert_eq!(
+            ty("list<string>").parse_text("a, b").unwrap(),
+            serde_yml::from_str::<Value>("[a, b]").unwrap()
+        );
+        assert_eq!(
+            ty("map").parse_text("{a: 1}").unwrap(),
+            serde_yml::from_str::<Value>("a: 1").unwrap()
+        );
+        assert_eq!(ty("float").parse_text("1.5").unwrap(), Value::from(1.5));
+    }
+
+    #[test]
+    fn test_derived_values() {
+        let dir = std::env::temp_dir();
+        let def: InputDef = serde_yml::from_str(
+            r#"{ type: int, from_command: [sh, -c, "echo '{\"a\": {\"b\": 3}}'"], json_pointer: /a/b }"#,
+        )
+        .unwrap();
+        assert_eq!(derived_value(&def, &dir).unwrap(), Some(Value::from(3)));
+
+        let def: InputDef = serde_yml::from_str(
+            r#"{ type: string, from_command: [sh, -c, "echo module example.com/app"], regex: "module (\\S+)" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            derived_value(&def, &dir).unwrap(),
+            Some(Value::from("example.com/app"))
+        );
+
+        let def: InputDef = serde_yml::from_str(
+            "{ type: string, from_command: [sh, -c, \"echo nope >&2; exit 3\"] }",
+        )
+        .unwrap();
+        let err = derived_value(&def, &dir).unwrap_err().to_string();
+        assert!(
+            err.contains("command `sh -c echo nope >&2; exit 3` failed"),
+            "{err}"
+        );
+        assert!(err.ends_with(": nope"), "{err}");
+
+        let def: InputDef =
+            serde_yml::from_str("{ type: string, from_command: [git], default: x }").unwrap();
+        assert!(check_derivation(&def).is_err());
+    }
+}
@@ -0,0 +1,470 @@
+//! `${...}` references inside `weaver.yaml` values.
+//!
+//! Supported references:
+//! * `${env:NAME}` and `${env:NAME:-default}`: environment variables,
+//! * `${apps.<app>.name}`, `${apps.<app>.path}`, `${apps.<app>.inputs.<key>}`:
+//!   values of other apps,
+//! * `${secret:<name>}`: a secret declared under `secrets:`.
+//!
+//! Environment and app references are resolved when the config is loaded.
+//! Secret references are only checked at that point and resolved when an
+//! app is instantiated, so their values live in [`Secret`] wrappers and
+//! never in the plain config. `$${` escapes a literal `${`.
+//!
+//! A string that consists of a single reference takes the referenced value
+//! as is (e.g. a number input); references embedded in a longer string are
+//! substituted as text.
+
+use crate::config::WeaverConfig;
+use crate::secret::{Secret, SecretResolver};
+use serde_yml::Value;
+use std::collections::HashMap;
+
+/// Looks up environment variables; injectable for tests.
+pub type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+#[derive(Debug, PartialEq)]
+enum Reference<'a> {
+    Env {
+        name: &'a str,
+        default: Option<&'a str>,
+    },
+    Secret(&'a str),
+    App {
+        app: &'a str,
+        field: Vec<&'a str>,
+    },
+}
+
+fn parse_reference(expr: &str) -> anyhow::Result<Reference<'_>> {
+    if let Some(rest) = expr.strip_prefix("env:") {
+        let (name, default) = match rest.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (rest, None),
+        };
+        if name.is_empty() {
+            anyhow::bail!("empty environment variable name in '${{{}}}'", expr);
+        }
+        return Ok(Reference::Env { name, default });
+    }
+    if let Some(name) = expr.strip_prefix("secret:") {
+        return Ok(Reference::Secret(name));
+    }
+    if let Some(rest) = expr.strip_prefix("apps.") {
+        let mut parts = rest.split('.');
+        let app = parts.next().unwrap_or_default();
+        let field: Vec<&str> = parts.collect();
+        let valid = match field.as_slice() {
+            ["name"] | ["path"] => true,
+            ["inputs", key, ..] => !key.is_empty(),
+            _ => false,
+        };
+        if app.is_empty() || !valid {
+            anyhow::bail!(
+                "unsupported reference '${{{}}}' (expected apps.<app>.name, apps.<app>.path or apps.<app>.inputs.<key>)",
+                expr
+            );
+        }
+        return Ok(Reference::App { app, field });
+    }
+    anyhow::bail!(
+        "unknown reference '${{{}}}' (expected env:, secret: or apps.)",
+        expr
+    )
+}
+
+/// A piece of an interpolated string.
+enum Segment<'a> {
+    Text(String),
+    Reference(&'a str),
+}
+
+fn split_segments(input: &str) -> anyhow::Result<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut rest = input;
+    while let Some(idx) = rest.find("${") {
+        if rest[..idx].ends_with('$') {
+            // `$${` is an escaped literal `${`
+            text.push_str(&rest[..idx - 1]);
+            text.push_str("${");
+            rest = &rest[idx + 2..];
+            continue;
+        }
+        text.push_str(&rest[..idx]);
+        let end = rest[idx..]
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("unterminated '${{' in '{}'", input))?;
+        if !text.is_empty() {
+            segments.push(Segment::Text(std::mem::take(&mut text)));
+        }
+        segments.push(Segment::Reference(&rest[idx + 2..idx + end]));
+        rest = &rest[idx + end + 1..];
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
+
+fn value_to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => serde_yml::to_string(other)
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Resolves env and app references of a loaded config in place.
+pub fn interpolate_config(config: &mut WeaverConfig, env: EnvLookup) -> anyhow::Result<()> {
+    let snapshot = config.clone();
+    let resolver = ConfigInterpolator {
+        config: &snapshot,
+        env,
+    };
+
+    for module in &mut config.modules {
+        let context = format!("Module '{}'", module.name);
+        module.source = resolver.module_field(&module.source, &context)?;
+        if let Some(r#ref) = &module.r#ref {
+            module.r#ref = Some(resolver.module_field(r#ref, &context)?);
+        }
+        if let Some(path) = &module.path {
+            module.path = Some(resolver.module_field(path, &context)?);
+        }
+    }
+
+    for app in &mut config.apps {
+        for (key, value) in app.inputs.iter_mut() {
+            let context = format!("App '{}': input '{}'", app.name, key);
+            let mut stack = vec![format!("apps.{}.inputs.{}", app.name, key)];
+            *value = resolver.value(value, &context, &mut stack)?;
+        }
+        for entry in &mut app.extends {
+            if let crate::config::ExtendsEntry::Detailed { module, inputs } = entry {
+                for (key, value) in inputs.iter_mut() {
+                    let context = format!("App '{}': input '{}' of '{}'", app.name, key, module);
+                    *value = resolver.value(value, &context, &mut Vec::new())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+struct ConfigInterpolator<'a> {
+    config: &'a WeaverConfig,
+    env: EnvLookup<'a>,
+}
+
+impl ConfigInterpolator<'_> {
+    fn module_field(&self, input: &str, context: &str) -> anyhow::Result<String> {
+        let mut out = String::new();
+        for segment in split_segments(input).map_err(|e| anyhow::anyhow!("{}: {}", context, e))? {
+            match segment {
+                Segment::Text(text) => out.push_str(&text),
+                Segment::Reference(expr) => {
+                    match parse_reference(expr)
+                        .map_err(|e| anyhow::anyhow!("{}: {}", context, e))?
+                    {
+                        Reference::Env { name, default } => {
+                            out.push_str(&self.env_var(name, default, context)?)
+                        }
+                        // Sources and refs end up in cache paths and logs
+                        _ => anyhow::bail!(
+                            "{}: only ${{env:...}} references are allowed in module sources",
+                            context
+                        ),
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    fn env_var(&self, name: &str, default: Option<&str>, context: &str) -> anyhow::Result<String> {
+        match ((self.env)(name), default) {
+            (Some(value), _) => Ok(value),
+            (None, Some(default)) => Ok(default.to_string()),
+            (None, None) => anyhow::bail!(
+                "{}: environment variable '{}' is not set (use ${{env:{}:-default}} for a fallback)",
+                context,
+                name,
+                name
+            ),
+        }
+    }
+
+    /// `stack` holds the app inputs being resolved, to detect cycles.
+    fn value(
+        &self,
+        value: &Value,
+        context: &str,
+        stack: &mut Vec<String>,
+    ) -> anyhow::Result<Value> {
+        match value {
+            Value::String(s) => self.string(s, context, stack),
+            Value::Sequence(items) => items
+                .iter()
+                .map(|item| self.value(item, context, stack))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map(Value::Sequence),
+            Value::Mapping(map) => {
+                let mut out = serde_yml::Mapping::new();
+                for (k, v) in map {
+                    out.insert(k.clone(), self.value(v, context, stack)?);
+                }
+                Ok(Value::Mapping(out))
+            }
+            other => Ok(other.clone()),
+        }
+    }
+
+    fn string(&self, input: &str, context: &str, stack: &mut Vec<String>) -> anyhow::Result<Value> {
+        let segments = split_segments(input).map_err(|e| anyhow::anyhow!("{}: {}", context, e))?;
+        let whole = segments.len() == 1;
+        let mut out = String::new();
+        for segment in segments {
+            let expr = match segment {
+                Segment::Text(text) => {
+                    out.push_str(&escape(&text));
+                    continue;
+                }
+                Segment::Reference(expr) => expr,
+            };
+            let resolved =
+                match parse_reference(expr).map_err(|e| anyhow::anyhow!("{}: {}", context, e))? {
+                    Reference::Env { name, default } => {
+                        Value::String(escape(&self.env_var(name, default, context)?))
+                    }
+                    Reference::Secret(name) => {
+                        if !self.config.secrets.contains_key(name) {
+                            anyhow::bail!(
+                                "{}: secret '{}' is not declared under 'secrets'",
+                                context,
+                                name
+                            );
+                        }
+                        // Kept as a reference until the app is instantiated
+                        Value::String(format!("${{{}}}", expr))
+                    }
+                    Reference::App { app, field } => self.app_field(app, &field, context, stack)?,
+                };
+            if whole {
+                return Ok(resolved);
+            }
+            out.push_str(&value_to_text(&resolved));
+        }
+        Ok(Value::String(out))
+    }
+
+    fn app_field(
+        &self,
+        app_name: &str,
+        field: &[&str],
+        context: &str,
+        stack: &mut Vec<String>,
+    ) -> anyhow::Result<Value> {
+        let app = self
+            .config
+            .apps
+            .iter()
+            .find(|a| a.name == app_name)
+            .ok_or_else(|| anyhow::anyhow!("{}: app '{}' not found", context, app_name))?;
+
+        match field {
+            ["name"] => Ok(Value::String(escape(&app.name))),
+            ["path"] => Ok(Value::String(escape(&app.path))),
+            ["inputs", key, rest @ ..] => {
+                let id = format!("apps.{}.inputs.{}", app_name, key);
+                if stack.contains(&id) {
+                    stack.push(id);
+                    anyhow::bail!("{}: circular reference {}", context, stack.join(" -> "));
+                }
+                let raw = app.inputs.get(*key).ok_or_else(|| {
+                    anyhow::anyhow!("{}: app '{}' has no input '{}'", context, app_name, key)
+                })?;
+                stack.push(id);
+                let mut value = self.value(raw, context, stack)?;
+                stack.pop();
+                for segment in rest {
+                    value = value.get(*segment).cloned().ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "{}: input '{}' of app '{}' has no field '{}'",
+                            context,
+                            key,
+                            app_name,
+                            segment
+                        )
+                    })?;
+                }
+                Ok(value)
+            }
+            _ => unreachable!("validated by parse_reference"),
+        }
+    }
+}
+
+/// Escapes resolved text so a later pass doesn't read it as a reference.
+fn escape(text: &str) -> String {
+    text.replace("${", "$${")
+}
+
+/// Whether `value` holds `${secret:...}` references that are only resolved
+/// when the app is instantiated.
+pub fn contains_secret(value: &Value) -> bool {
+    match value {
+        Value::String(s) => split_segments(s).is_ok_and(|segments| {
+            segments
+                .iter()
+                .any(|s| matches!(s, Segment::Reference(expr) if expr.starts_with("secret:")))
+        }),
+        Value::Sequence(items) => items.iter().any(contains_secret),
+        Value::Mapping(map) => map.values().any(contains_secret),
+        _ => false,
+    }
+}
+
+/// Resolves the `${secret:...}` references [`interpolate_config`] left in
+/// place and unescapes literal `$${`.
+fn resolve_deferred(value: &Value, secrets: &SecretResolver) -> anyhow::Result<Value> {
+    match value {
+        Value::String(s) => {
+            let mut out = String::new();
+            for segment in split_segments(s)? {
+                match segment {
+                    Segment::Text(text) => out.push_str(&text),
+                    Segment::Reference(expr) => {
+                        let name = expr.strip_prefix("secret:").ok_or_else(|| {
+                            anyhow::anyhow!("unresolved reference '${{{}}}'", expr)
+                        })?;
+                        let secret = secrets.resolve(name)?;
+                        out.push_str(secret.expose());
+                    }
+                }
+            }
+            Ok(Value::String(out))
+        }
+        Value::Sequence(items) => items
+            .iter()
+            .map(|item| resolve_deferred(item, secrets))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(Value::Sequence),
+        Value::Mapping(map) => {
+            let mut out = serde_yml::Mapping::new();
+            for (k, v) in map {
+                out.insert(k.clone(), resolve_deferred(v, secrets)?);
+            }
+            Ok(Value::Mapping(out))
+        }
+        other => Ok(other.clone()),
+    }
+}
+
+/// Plain and secret inputs of an app layer.
+pub type ResolvedInputs = (HashMap<String, Value>, HashMap<String, Secret<Value>>);
+
+/// Resolves the secrets in app inputs loaded by [`WeaverConfig::load`].
+/// Inputs built from a secret are returned separately, wrapped in [`Secret`].
+pub fn resolve_inputs(
+    inputs: &HashMap<String, Value>,
+    secrets: &SecretResolver,
+) -> anyhow::Result<ResolvedInputs> {
+    let mut plain = HashMap::new();
+    let mut hidden = HashMap::new();
+    for (key, value) in inputs {
+        let resolved = resolve_deferred(value, secrets)
+            .map_err(|e| anyhow::anyhow!("input '{}': {}", key, e))?;
+        if contains_secret(value) {
+            hidden.insert(key.clone(), Secret::new(resolved));
+        } else {
+            plain.insert(key.clone(), resolved);
+        }
+    }
+    Ok((plain, hidden))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(yaml: &str, env: &[(&str, &str)]) -> anyhow::Result<WeaverConfig> {
+        let mut config: WeaverConfig = serde_yml::from_str(yaml).unwrap();
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        interpolate_config(&mut config, &|name| env.get(name).cloned())?;
+        Ok(config)
+    }
+
+    const CONFIG: &str = r#"
+version: "1"
+modules:
+  - { name: net, source: "git@example.com:${env:ORG}/net.git", ref: "${env:NET_REF:-v1}" }
+apps:
+  - name: network
+    module: net
+    path: infra/network
+    inputs: { cidr: "10.0.0.0/16", zones: 3 }
+  - name: api
+    module: net
+    path: api
+    inputs:
+      region: "${env:AWS_REGION}"
+      network_dir: "${apps.network.path}"
+      zones: "${apps.network.inputs.zones}"
+      label: "zones=${apps.network.inputs.zones} in $${literal}"
+      password: "${secret:db_password}"
+secrets:
+  db_password: { provider: env, key: RW_TEST_INTERPOLATE_DB_PASSWORD }
+"#;
+
+    #[test]
+    fn test_env_and_app_references() {
+        let config = load(CONFIG, &[("ORG", "acme"), ("AWS_REGION", "eu-west-1")]).unwrap();
+        assert_eq!(config.modules[0].source, "git@example.com:acme/net.git");
+        assert_eq!(config.modules[0].r#ref.as_deref(), Some("v1"));
+
+        let inputs = &config.apps[1].inputs;
+        assert_eq!(inputs["region"], Value::from("eu-west-1"));
+        assert_eq!(inputs["network_dir"], Value::from("infra/network"));
+        assert_eq!(inputs["zones"], Value::from(3));
+        assert_eq!(inputs["password"], Value::from("${secret:db_password}"));
+
+        let resolver = SecretResolver::new(config.secrets.clone(), Vec::new()).with_env(|name| {
+            (name == "RW_TEST_INTERPOLATE_DB_PASSWORD").then(|| "hunter2".to_string())
+        });
+        let (plain, hidden) = resolve_inputs(inputs, &resolver).unwrap();
+        assert_eq!(plain["label"], Value::from("zones=3 in ${literal}"));
+        assert!(hidden.contains_key("password"));
+        assert!(!plain.contains_key("password"));
+        assert_eq!(hidden["password"].expose(), &Value::from("hunter2"));
+    }
+
+    #[test]
+    fn test_unset_env_names_the_variable() {
+        let err = load(CONFIG, &[("ORG", "acme")]).unwrap_err().to_string();
+        assert!(err.contains("App 'api': input 'region'"), "{err}");
+        assert!(err.contains("'AWS_REGION' is not set"), "{err}");
+    }
+
+    #[test]
+    fn test_circular_app_references() {
+        let err = load(
+            r#"
+version: "1"
+apps:
+  - { name: a, module: m, path: a, inputs: { x: "${apps.b.inputs.y}" } }
+  - { name: b, module: m, path: b, inputs: { y: "${apps.a.inputs.x}" } }
+"#,
+            &[],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("circular reference"), "{err}");
+    }
+}
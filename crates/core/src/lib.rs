@@ -1,12 +1,20 @@
+pub mod answers;
 pub mod app;
+pub mod cipher;
 pub mod config;
 pub mod engine;
+pub mod filters;
+pub mod input;
+pub mod interpolate;
+pub mod loader;
 pub mod lockfile;
 pub mod logging;
+pub mod migrate;
 pub mod module;
 pub mod plugin;
 pub mod secret;
 pub mod state;
 pub mod template;
+pub mod validate;
 
 pub use logging::{LoggingOptions, setup_tracing, setup_tracing_with_options};
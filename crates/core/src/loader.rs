@@ -0,0 +1,651 @@
+//! Loading of the root `weaver.yaml` together with its config fragments.
+//!
+//! Fragments are merged into the root document in a stable order:
+//!
+//! 1. the root file itself,
+//! 2. every file matched by the root `includes:` globs, in the order the
+//!    patterns are listed (matches of a single pattern are sorted by path),
+//! 3. every file matched by the implicit `weaver.d/**/*.yaml` glob.
+//!
+//! A file is only loaded once, at the position where it is first matched.
+//!
+//! Merge rules:
+//! * maps are deep-merged (e.g. app `inputs`, `secrets`),
+//! * lists are concatenated (e.g. `modules`, `apps`),
+//! * scalars from later files override earlier ones.
+//!
+//! `apps` and `modules` entries sharing a `name` are merged into one entry
+//! using the same rules. An entry whose identity differs between files (an
+//! app bound to another `module` or `extends` list, or a module pointing at
+//! another source) is a conflict and reported with both files.
+//!
+//! When `discovery.enabled` is set, the workspace is walked for app-local
+//! `weaver.app.yaml` files after all fragments are merged. Each one holds a
+//! single app definition whose `path` is relative to the folder containing
+//! the file (and defaults to that folder).
+
+use crate::config::DiscoveryConfig;
+use crate::migrate::{self, DocumentKind};
+use serde_yml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Glob loaded after the explicit `includes:`, relative to the root file.
+pub const IMPLICIT_INCLUDE: &str = "weaver.d/**/*.yaml";
+
+/// File name of app definitions discovered next to the app's code.
+pub const APP_FILE_NAME: &str = "weaver.app.yaml";
+
+/// Result of merging the root file with all of its fragments.
+pub struct MergedDocument {
+    pub value: Value,
+    /// Every file that contributed to `value`, in load order.
+    pub sources: Vec<PathBuf>,
+    /// File that first declared each app, keyed by app name.
+    pub app_origins: HashMap<String, PathBuf>,
+}
+
+pub fn load_merged(root_path: &Path) -> anyhow::Result<MergedDocument> {
+    let root = read_document(root_path)?;
+    migrate::check_version(
+        DocumentKind::Config,
+        &Value::Mapping(root.clone()),
+        root_path,
+        true,
+    )?;
+    let base_dir = base_dir(root_path);
+
+    let mut patterns: Vec<String> = match root.get("includes") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(value) => serde_yml::from_value(value.clone()).map_err(|e| {
+            anyhow::anyhow!(
+                "{}: 'includes' must be a list of globs: {}",
+                root_path.display(),
+                e
+            )
+        })?,
+    };
+    patterns.push(IMPLICIT_INCLUDE.to_string());
+
+    let mut merger = Merger::default();
+    merger.merge_document(root, root_path)?;
+
+    for (idx, pattern) in patterns.iter().enumerate() {
+        let implicit = idx == patterns.len() - 1;
+        for fragment_path in expand_include(base_dir, pattern, implicit)? {
+            if merger.sources.contains(&fragment_path) {
+                continue;
+            }
+            let fragment = read_document(&fragment_path)?;
+            migrate::check_version(
+                DocumentKind::Config,
+                &Value::Mapping(fragment.clone()),
+                &fragment_path,
+                false,
+            )?;
+            if fragment.contains_key("includes") {
+                anyhow::bail!(
+                    "{}: 'includes' is only supported in the root config file",
+                    fragment_path.display()
+                );
+            }
+            merger.merge_document(fragment, &fragment_path)?;
+        }
+    }
+
+    let discovery = match merger.root.get("discovery") {
+        None | Some(Value::Null) => DiscoveryConfig::default(),
+        Some(value) => serde_yml::from_value(value.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid 'discovery' section: {}", e))?,
+    };
+    if discovery.enabled {
+        for app_file in discover_app_files(base_dir, &discovery.exclude)? {
+            let app = read_app_file(base_dir, &app_file)?;
+            merger.merge_named_list("apps", Value::Sequence(vec![app]), &app_file)?;
+            merger.sources.push(app_file);
+        }
+    }
+
+    Ok(MergedDocument {
+        value: Value::Mapping(merger.root),
+        sources: merger.sources,
+        app_origins: merger
+            .origins
+            .remove("apps")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, (_, path))| (name, path))
+            .collect(),
+    })
+}
+
+/// Lists the root file, its fragments and (when discovery is enabled) app
+/// files in load order, without merging or validating them.
+pub fn config_files(root_path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let root = read_document(root_path)?;
+    let base_dir = base_dir(root_path);
+
+    let mut files = vec![root_path.to_path_buf()];
+    let mut patterns: Vec<String> = match root.get("includes") {
+        Some(Value::Sequence(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    };
+    patterns.push(IMPLICIT_INCLUDE.to_string());
+    for (idx, pattern) in patterns.iter().enumerate() {
+        for path in expand_include(base_dir, pattern, idx == patterns.len() - 1)? {
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    let discovery: DiscoveryConfig = root
+        .get("discovery")
+        .and_then(|value| serde_yml::from_value(value.clone()).ok())
+        .unwrap_or_default();
+    if discovery.enabled {
+        files.extend(discover_app_files(base_dir, &discovery.exclude)?);
+    }
+    Ok(files)
+}
+
+fn base_dir(root_path: &Path) -> &Path {
+    match root_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+fn read_document(path: &Path) -> anyhow::Result<Mapping> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+    match serde_yml::from_str::<Value>(&content)
+        .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?
+    {
+        Value::Mapping(map) => Ok(map),
+        // An empty fragment is harmless, treat it as an empty mapping
+        Value::Null => Ok(Mapping::new()),
+        _ => anyhow::bail!("{}: expected a mapping at the top level", path.display()),
+    }
+}
+
+/// Folders never searched for app files, besides hidden ones.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target"];
+
+/// Walks `base_dir` for app files, skipping hidden directories,
+/// [`SKIPPED_DIRS`] and paths matching one of the `exclude` globs. As in
+/// `files` rules, `*` does not cross `/`. Results are sorted by path.
+fn discover_app_files(base_dir: &Path, exclude: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let patterns = exclude
+        .iter()
+        .map(|p| {
+            glob::Pattern::new(p)
+                .map_err(|e| anyhow::anyhow!("Invalid discovery exclude '{}': {}", p, e))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let walker = WalkDir::new(base_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+            let name = entry.file_name().to_string_lossy();
+            if entry.file_type().is_dir()
+                && (name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()))
+            {
+                return false;
+            }
+            let rel = entry.path().strip_prefix(base_dir).unwrap_or(entry.path());
+            let options = glob::MatchOptions {
+                require_literal_separator: true,
+                ..Default::default()
+            };
+            !patterns.iter().any(|p| p.matches_path_with(rel, options))
+        });
+
+    let mut found = Vec::new();
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.file_name() == APP_FILE_NAME {
+            found.push(entry.into_path());
+        }
+    }
+    Ok(found)
+}
+
+/// Reads an app-local definition and rewrites its `path` relative to the
+/// workspace root.
+fn read_app_file(base_dir: &Path, app_file: &Path) -> anyhow::Result<Value> {
+    let mut app = read_document(app_file)?;
+    migrate::check_version(
+        DocumentKind::Config,
+        &Value::Mapping(app.clone()),
+        app_file,
+        false,
+    )?;
+    app.remove("version");
+    let app_dir = app_file
+        .parent()
+        .unwrap_or(base_dir)
+        .strip_prefix(base_dir)
+        .unwrap_or(Path::new(""));
+
+    let local_path = match app.get("path") {
+        None | Some(Value::Null) => PathBuf::new(),
+        Some(Value::String(p)) => PathBuf::from(p),
+        Some(_) => anyhow::bail!("{}: 'path' must be a string", app_file.display()),
+    };
+    if local_path.is_absolute() {
+        anyhow::bail!(
+            "{}: 'path' must be relative to the folder of the app file",
+            app_file.display()
+        );
+    }
+
+    let mut path = String::new();
+    for component in app_dir.join(local_path).components() {
+        match component {
+            std::path::Component::CurDir => {}
+            other => {
+                if !path.is_empty() {
+                    path.push('/');
+                }
+                path.push_str(&other.as_os_str().to_string_lossy());
+            }
+        }
+    }
+    if path.is_empty() {
+        path.push('.');
+    }
+    app.insert(Value::from("path"), Value::from(path));
+
+    if !app.get("name").is_some_and(Value::is_string) {
+        anyhow::bail!("{}: app files must declare a 'name'", app_file.display());
+    }
+    Ok(Value::Mapping(app))
+}
+
+fn expand_include(base_dir: &Path, pattern: &str, implicit: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let full = base_dir.join(pattern);
+    let full_str = full.to_string_lossy();
+
+    let is_glob = pattern.contains(['*', '?', '[']);
+    if !is_glob && !implicit {
+        if !full.is_file() {
+            anyhow::bail!("Included file not found: {}", full.display());
+        }
+        return Ok(vec![full]);
+    }
+
+    let mut matches = Vec::new();
+    for entry in glob::glob(&full_str)
+        .map_err(|e| anyhow::anyhow!("Invalid include pattern '{}': {}", pattern, e))?
+    {
+        let path = entry?;
+        if path.is_file() {
+            matches.push(path);
+        }
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+/// Fields that identify what a named list entry *is*. Two entries with the
+/// same name but a different identity cannot be merged.
+fn identity_keys(section: &str) -> Option<&'static [&'static str]> {
+    match section {
+        "apps" => Some(&["module", "extends"]),
+        "modules" => Some(&["source"]),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Merger {
+    root: Mapping,
+    sources: Vec<PathBuf>,
+    /// section -> entry name -> (index in the merged list, declaring file)
+    origins: HashMap<String, HashMap<String, (usize, PathBuf)>>,
+}
+
+impl Merger {
+    fn merge_document(&mut self, doc: Mapping, path: &Path) -> anyhow::Result<()> {
+        for (key, value) in doc {
+            let section = key.as_str().unwrap_or_default().to_string();
+            if identity_keys(&section).is_some() {
+                self.merge_named_list(&section, value, path)?;
+            } else {
+                match self.root.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        self.root.insert(key, value);
+                    }
+                }
+            }
+        }
+        self.sources.push(path.to_path_buf());
+        Ok(())
+    }
+
+    fn merge_named_list(&mut self, section: &str, value: Value, path: &Path) -> anyhow::Result<()> {
+        let entries = match value {
+            Value::Sequence(entries) => entries,
+            Value::Null => return Ok(()),
+            _ => anyhow::bail!("{}: '{}' must be a list", path.display(), section),
+        };
+
+        let key = Value::String(section.to_string());
+        if !self.root.contains_key(&key) {
+            self.root.insert(key.clone(), Value::Sequence(Vec::new()));
+        }
+        let Some(Value::Sequence(list)) = self.root.get_mut(&key) else {
+            anyhow::bail!("{}: '{}' must be a list", path.display(), section);
+        };
+        let origins = self.origins.entry(section.to_string()).or_default();
+        let identity = identity_keys(section).unwrap_or_default();
+        let mut seen_in_file = HashSet::new();
+
+        for entry in entries {
+            let name = entry
+                .get("name")
+                .and_then(Value::as_str)
+                .map(str::to_string);
+
+            let Some(name) = name else {
+                // Unnamed entries can't collide; leave them for schema validation
+                list.push(entry);
+                continue;
+            };
+            if !seen_in_file.insert(name.clone()) {
+                anyhow::bail!(
+                    "{}: duplicate {} '{}'",
+                    path.display(),
+                    section.trim_end_matches('s'),
+                    name
+                );
+            }
+
+            match origins.get(&name) {
+                None => {
+                    origins.insert(name, (list.len(), path.to_path_buf()));
+                    list.push(entry);
+                }
+                Some((idx, first_path)) => {
+                    let existing = &mut list[*idx];
+                    let mut entry = entry;
+                    // The identity keys form one identity: `module: a` in one
+                    // file and `extends: [b]` in another conflict too
+                    let old_id = identity_of(existing, identity);
+                    let new_id = identity_of(&entry, identity);
+                    if !old_id.is_empty() && !new_id.is_empty() && old_id != new_id {
+                        anyhow::bail!(
+                            "Conflicting definitions of {} '{}': {} in {} but {} in {}",
+                            section.trim_end_matches('s'),
+                            name,
+                            describe_identity(&old_id),
+                            first_path.display(),
+                            describe_identity(&new_id),
+                            path.display()
+                        );
+                    }
+                    // Identical identity: keep it as is instead of concatenating lists
+                    if !old_id.is_empty()
+                        && let Value::Mapping(map) = &mut entry
+                    {
+                        for key in identity {
+                            map.remove(*key);
+                        }
+                    }
+                    deep_merge(existing, entry);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The identity keys `entry` sets, with their values.
+fn identity_of(entry: &Value, keys: &[&'static str]) -> Vec<(&'static str, Value)> {
+    keys.iter()
+        .filter_map(|key| Some((*key, entry.get(key)?.clone())))
+        .collect()
+}
+
+fn describe_identity(identity: &[(&str, Value)]) -> String {
+    identity
+        .iter()
+        .map(|(key, value)| format!("{} '{}'", key, display_scalar(value)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn display_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
+
+/// Deep-merges `overlay` into `base`: maps are merged key by key, lists are
+/// concatenated and any other value in `overlay` replaces the one in `base`.
+pub fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base_map), Value::Mapping(overlay_map)) => {
+            for (key, value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        base_map.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base_seq), Value::Sequence(overlay_seq)) => {
+            base_seq.extend(overlay_seq);
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, rel: &str, content: &str) -> PathBuf {
+        let path = dir.join(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_fragments_merge_in_stable_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = write(
+            dir.path(),
+            "weaver.yaml",
+            r#"
+version: "1"
+includes: ["teams/*.yaml"]
+modules:
+  - { name: svc, source: "git@example.com:svc.git", ref: v1 }
+apps:
+  - name: api
+    module: svc
+    path: api
+    inputs: { region: eu-west-1, tags: [a], db: { size: small } }
+"#,
+        );
+        write(
+            dir.path(),
+            "teams/b.yaml",
+            "apps:\n  - { name: web, module: svc, path: web }\n",
+        );
+        write(
+            dir.path(),
+            "teams/a.yaml",
+            "apps:\n  - name: api\n    module: svc\n    path: api\n    inputs: { tags: [b], db: { replicas: 2 } }\n",
+        );
+        write(
+            dir.path(),
+            "weaver.d/prod/override.yaml",
+            "apps:\n  - name: api\n    inputs: { region: us-east-1 }\n",
+        );
+
+        let merged = load_merged(&root).unwrap();
+        let apps = merged.value.get("apps").unwrap().as_sequence().unwrap();
+        assert_eq!(apps.len(), 2);
+        assert_eq!(apps[0]["name"], Value::from("api"));
+        assert_eq!(apps[1]["name"], Value::from("web"));
+
+        let inputs = &apps[0]["inputs"];
+        assert_eq!(inputs["region"], Value::from("us-east-1"));
+        assert_eq!(
+            inputs["tags"],
+            Value::Sequence(vec![Value::from("a"), Value::from("b")])
+        );
+        assert_eq!(inputs["db"]["size"], Value::from("small"));
+        assert_eq!(inputs["db"]["replicas"], Value::from(2));
+
+        let names: Vec<_> = merged
+            .sources
+            .iter()
+            .map(|p| p.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("weaver.yaml"),
+                PathBuf::from("teams/a.yaml"),
+                PathBuf::from("teams/b.yaml"),
+                PathBuf::from("weaver.d/prod/override.yaml"),
+            ]
+        );
+        assert_eq!(merged.app_origins["api"], root);
+    }
+
+    #[test]
+    fn test_conflicting_app_module_names_both_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = write(
+            dir.path(),
+            "weaver.yaml",
+            "version: \"1\"\napps:\n  - { name: api, module: svc, path: api }\n",
+        );
+        write(
+            dir.path(),
+            "weaver.d/api.yaml",
+            "apps:\n  - { name: api, module: other, path: api }\n",
+        );
+
+        let err = load_merged(&root).err().unwrap().to_string();
+        assert!(err.contains("app 'api'"), "{err}");
+        assert!(err.contains("weaver.yaml"), "{err}");
+        assert!(err.contains("weaver.d/api.yaml"), "{err}");
+    }
+
+    #[test]
+    fn test_module_and_extends_are_one_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = write(
+            dir.path(),
+            "weaver.yaml",
+            "version: \"1\"\napps:\n  - { name: api, module: a, path: api }\n",
+        );
+        write(
+            dir.path(),
+            "weaver.d/api.yaml",
+            "apps:\n  - { name: api, extends: [b] }\n",
+        );
+
+        let err = load_merged(&root).err().unwrap().to_string();
+        assert!(
+            err.contains("module 'a' in") && err.contains("but extends '[\"b\"]' in"),
+            "{err}"
+        );
+
+        // Restating the same identity still merges
+        write(
+            dir.path(),
+            "weaver.d/api.yaml",
+            "apps:\n  - { name: api, module: a, inputs: { region: eu } }\n",
+        );
+        let merged = load_merged(&root).unwrap();
+        let app = &merged.value["apps"][0];
+        assert_eq!(app["module"], Value::from("a"));
+        assert_eq!(app["inputs"]["region"], Value::from("eu"));
+    }
+
+    #[test]
+    fn test_discovered_app_files_default_path_to_their_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = write(
+            dir.path(),
+            "weaver.yaml",
+            "version: \"1\"\ndiscovery:\n  enabled: true\n  exclude: [\"vendor/**\", \"services/*-old\"]\n",
+        );
+        write(
+            dir.path(),
+            "services/billing/weaver.app.yaml",
+            "name: billing\nmodule: svc\ninputs: { port: 8080 }\n",
+        );
+        write(
+            dir.path(),
+            "services/web/weaver.app.yaml",
+            "name: web\nmodule: svc\npath: ./frontend\n",
+        );
+        write(
+            dir.path(),
+            "vendor/lib/weaver.app.yaml",
+            "name: vendored\nmodule: svc\n",
+        );
+        write(
+            dir.path(),
+            "services/billing-old/weaver.app.yaml",
+            "name: billing-old\nmodule: svc\n",
+        );
+        // `*` stays within one folder, so this one is searched
+        write(
+            dir.path(),
+            "services/web/admin-old/weaver.app.yaml",
+            "name: admin\nmodule: svc\n",
+        );
+        for skipped in ["node_modules/pkg", "target/debug"] {
+            write(
+                dir.path(),
+                &format!("{skipped}/weaver.app.yaml"),
+                "name: skipped\nmodule: svc\n",
+            );
+        }
+
+        let merged = load_merged(&root).unwrap();
+        let apps = merged.value.get("apps").unwrap().as_sequence().unwrap();
+        let names: Vec<&str> = apps.iter().map(|a| a["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["billing", "admin", "web"]);
+        assert_eq!(apps[0]["path"], Value::from("services/billing"));
+        assert_eq!(apps[2]["path"], Value::from("services/web/frontend"));
+        assert_eq!(
+            merged.app_origins["billing"],
+            dir.path().join("services/billing/weaver.app.yaml")
+        );
+    }
+
+    #[test]
+    fn test_missing_explicit_include_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = write(
+            dir.path(),
+            "weaver.yaml",
+            "version: \"1\"\nincludes: [extra.yaml]\n",
+        );
+
+        let err = load_merged(&root).err().unwrap().to_string();
+        assert!(err.contains("extra.yaml"), "{err}");
+    }
+}
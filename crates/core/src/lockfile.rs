@@ -1,15 +1,55 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Current `weaver.lock` layout.
+pub const LOCKFILE_VERSION: &str = "1";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Lockfile {
     pub version: String,
-    pub modules: HashMap<String, ModuleLock>,
+    /// Locked modules by module name
+    pub modules: BTreeMap<String, ModuleLock>,
+}
+
+impl Default for Lockfile {
+    fn default() -> Self {
+        Self {
+            version: LOCKFILE_VERSION.to_string(),
+            modules: BTreeMap::new(),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ModuleLock {
     pub source: String,
-    pub r#ref: String,
+    /// Requested ref, absent for local sources
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub r#ref: Option<String>,
+    /// Subdirectory of the source holding the module
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Commit SHA for git sources, `sha256:<hex>` content hash for local ones
     pub checksum: String,
 }
+
+impl Lockfile {
+    /// Loads `path`, or returns `None` when there is no lockfile yet.
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)?;
+        let lockfile = serde_yml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        Ok(Some(lockfile))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content = serde_yml::to_string(self)?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+//! Schema versions of the files rw reads and the migrations between them.
+//!
+//! Every document kind has a current version. Loaders refuse documents from
+//! a newer rw and point at `rw migrate` for documents older than what they
+//! can read. `rw migrate` applies the registered [`Migration`] steps one
+//! version at a time.
+
+use serde_yml::{Mapping, Value};
+use std::path::Path;
+
+/// Schema version of `weaver.yaml`, its fragments and app files.
+pub const CONFIG_VERSION: u32 = 1;
+/// Schema version of `weaver.module.yaml`.
+pub const MANIFEST_VERSION: u32 = 1;
+/// Schema version of `.rw/state.yaml`.
+pub const STATE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Config,
+    Manifest,
+    State,
+}
+
+impl DocumentKind {
+    pub fn current_version(self) -> u32 {
+        match self {
+            DocumentKind::Config => CONFIG_VERSION,
+            DocumentKind::Manifest => MANIFEST_VERSION,
+            DocumentKind::State => STATE_VERSION,
+        }
+    }
+
+    /// Version assumed when a document has no `version` key.
+    fn unversioned(self) -> u32 {
+        match self {
+            // Manifests were unversioned before version 1 was introduced
+            DocumentKind::Config | DocumentKind::Manifest => 1,
+            DocumentKind::State => 0,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            DocumentKind::Config => "config",
+            DocumentKind::Manifest => "module manifest",
+            DocumentKind::State => "state file",
+        }
+    }
+}
+
+/// A rewrite of one document kind from `from` to `from + 1`.
+struct Migration {
+    kind: DocumentKind,
+    from: u32,
+    apply: fn(&mut Mapping) -> anyhow::Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    kind: DocumentKind::State,
+    from: 0,
+    // Version 0 is the unversioned layout, which only lacks the version key
+    apply: |_| Ok(()),
+}];
+
+/// Reads the major version of a document. `"1"`, `"1.0"` and `1` are all
+/// version 1; a missing key yields `None`.
+pub fn document_version(doc: &Value) -> anyhow::Result<Option<u32>> {
+    let raw = match doc.get("version") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Number(n)) => n.to_string(),
+        Some(other) => anyhow::bail!("'version' must be a string, got {:?}", other),
+    };
+    let major = raw.split('.').next().unwrap_or_default();
+    major
+        .trim()
+        .parse::<u32>()
+        .map(Some)
+        .map_err(|_| anyhow::anyhow!("Invalid schema version '{}'", raw))
+}
+
+/// Fails unless `doc` can be read as the current version of `kind`.
+/// `required` rejects documents without a `version` key.
+pub fn check_version(
+    kind: DocumentKind,
+    doc: &Value,
+    path: &Path,
+    required: bool,
+) -> anyhow::Result<()> {
+    let version =
+        document_version(doc).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    let version = match version {
+        Some(version) => version,
+        None if required => anyhow::bail!(
+            "{}: missing 'version' (current {} version is \"{}\")",
+            path.display(),
+            kind.label(),
+            kind.current_version()
+        ),
+        None => kind.unversioned(),
+    };
+
+    let current = kind.current_version();
+    if version > current {
+        anyhow::bail!(
+            "{}: {} version {} is newer than this rw supports ({}). Please upgrade rw.",
+            path.display(),
+            kind.label(),
+            version,
+            current
+        );
+    }
+    if version < current {
+        anyhow::bail!(
+            "{}: {} version {} is no longer supported (current is {}). Run `rw migrate` to upgrade it.",
+            path.display(),
+            kind.label(),
+            version,
+            current
+        );
+    }
+    Ok(())
+}
+
+/// Upgrades `doc` to the current version of `kind` in place. Returns whether
+/// anything changed.
+pub fn migrate(kind: DocumentKind, doc: &mut Value) -> anyhow::Result<bool> {
+    let Value::Mapping(map) = doc else {
+        anyhow::bail!("expected a mapping at the top level");
+    };
+    let declared = document_version(&Value::Mapping(map.clone()))?;
+    let mut version = declared.unwrap_or(kind.unversioned());
+    let current = kind.current_version();
+    if version > current {
+        anyhow::bail!(
+            "{} version {} is newer than this rw supports ({})",
+            kind.label(),
+            version,
+            current
+        );
+    }
+
+    let mut changed = false;
+    while version < current {
+        let step = MIGRATIONS
+            .iter()
+            .find(|m| m.kind == kind && m.from == version)
+            .ok_or_else(|| {
+                anyhow::anyhow!("No migration for {} version {}", kind.label(), version)
+            })?;
+        (step.apply)(map)?;
+        version += 1;
+        changed = true;
+    }
+
+    // Migrated documents get the canonical spelling of their new version.
+    // Up-to-date ones are left alone, whatever their spelling (`1`, "1.0")
+    if changed {
+        let canonical = Value::String(version.to_string());
+        if map.contains_key("version") {
+            map.insert(Value::from("version"), canonical);
+        } else {
+            // Keep the version as the first key, as `rw init` writes it
+            let mut reordered = Mapping::new();
+            reordered.insert(Value::from("version"), canonical);
+            reordered.extend(std::mem::take(map));
+            *map = reordered;
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(s: &str) -> Value {
+        serde_yml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_version_spellings() {
+        assert_eq!(
+            document_version(&yaml("version: \"1.0\"")).unwrap(),
+            Some(1)
+        );
+        assert_eq!(document_version(&yaml("version: 1")).unwrap(), Some(1));
+        assert_eq!(document_version(&yaml("apps: []")).unwrap(), None);
+        assert!(document_version(&yaml("version: latest")).is_err());
+    }
+
+    #[test]
+    fn test_check_version_rejects_newer_and_missing() {
+        let path = Path::new("weaver.yaml");
+        let err = check_version(DocumentKind::Config, &yaml("version: \"9\""), path, true)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("upgrade rw"), "{err}");
+        assert!(check_version(DocumentKind::Config, &yaml("apps: []"), path, true).is_err());
+        assert!(check_version(DocumentKind::Config, &yaml("apps: []"), path, false).is_ok());
+    }
+
+    #[test]
+    fn test_migrate_unversioned_state() {
+        let mut doc = yaml("files: {}");
+        assert!(check_version(DocumentKind::State, &doc, Path::new("state.yaml"), false).is_err());
+        assert!(migrate(DocumentKind::State, &mut doc).unwrap());
+        assert_eq!(doc, yaml("version: \"1\"\nfiles: {}"));
+        assert!(!migrate(DocumentKind::State, &mut doc).unwrap());
+    }
+
+    #[test]
+    fn test_migrate_leaves_current_spellings_alone() {
+        for spelling in ["version: \"1.0\"", "version: 1", "version: \"1\""] {
+            let mut doc = yaml(&format!("{spelling}\napps: []"));
+            assert!(
+                !migrate(DocumentKind::Config, &mut doc).unwrap(),
+                "{spelling}"
+            );
+            assert_eq!(doc, yaml(&format!("{spelling}\napps: []")));
+        }
+    }
+}
@@ -1,10 +1,52 @@
-use crate::lockfile::Lockfile;
+use crate::config::ModuleConfig;
+use crate::lockfile::{Lockfile, ModuleLock};
 use repo_weaver_ops::git;
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// Where a module's files come from.
+#[derive(Debug, PartialEq)]
+pub enum ModuleSource {
+    /// A directory read in place: `./dir`, `../dir`, an absolute path or `path:<dir>`.
+    /// Relative paths are relative to the workspace root.
+    Local(PathBuf),
+    /// A git repository cloned into the global cache.
+    Git(String),
+}
+
+impl ModuleSource {
+    pub fn parse(source: &str) -> Self {
+        if let Some(path) = source.strip_prefix("path:") {
+            return ModuleSource::Local(PathBuf::from(path));
+        }
+        if source == "."
+            || source.starts_with("./")
+            || source.starts_with("../")
+            || Path::new(source).is_absolute()
+        {
+            return ModuleSource::Local(PathBuf::from(source));
+        }
+        ModuleSource::Git(source.to_string())
+    }
+}
+
+/// A module available on disk.
+pub struct ResolvedModule {
+    pub path: PathBuf,
+    /// Entry recording what was resolved, for `weaver.lock`
+    pub lock: ModuleLock,
+}
 
 pub struct ModuleResolver {
     cache_dir: PathBuf,
     lockfile: Option<Lockfile>,
+    /// Checkouts resolved during this run by (url, ref), with their commit.
+    /// Modules stored in one repository share a single clone.
+    checkouts: Mutex<HashMap<(String, String), (PathBuf, String)>>,
 }
 
 impl ModuleResolver {
@@ -12,49 +54,272 @@ impl ModuleResolver {
         let home =
             home::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
         let cache_dir = home.join(".rw").join("store");
-        Ok(Self {
+        Ok(Self::with_cache_dir(cache_dir, lockfile))
+    }
+
+    pub fn with_cache_dir(cache_dir: PathBuf, lockfile: Option<Lockfile>) -> Self {
+        Self {
             cache_dir,
             lockfile,
+            checkouts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Makes `module` available on disk. The returned path is the module
+    /// root, i.e. the `path` subdirectory of the source when one is set.
+    pub fn resolve(&self, module: &ModuleConfig) -> anyhow::Result<ResolvedModule> {
+        let subdir = match &module.path {
+            Some(subdir) => {
+                if !Path::new(subdir)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+                {
+                    anyhow::bail!(
+                        "Module '{}': 'path' must be a relative path inside the source, got '{}'",
+                        module.name,
+                        subdir
+                    );
+                }
+                Some(subdir.as_str())
+            }
+            None => None,
+        };
+
+        let resolved = match ModuleSource::parse(&module.source) {
+            ModuleSource::Local(path) => self.resolve_local(module, path, subdir)?,
+            ModuleSource::Git(url) => self.resolve_git(module, &url, subdir)?,
+        };
+
+        if !resolved.path.join("weaver.module.yaml").exists() {
+            anyhow::bail!(
+                "Module '{}': no weaver.module.yaml in {}",
+                module.name,
+                describe_location(module)
+            );
+        }
+        Ok(resolved)
+    }
+
+    fn resolve_local(
+        &self,
+        module: &ModuleConfig,
+        source_path: PathBuf,
+        subdir: Option<&str>,
+    ) -> anyhow::Result<ResolvedModule> {
+        let path = match subdir {
+            Some(subdir) => source_path.join(subdir),
+            None => source_path,
+        };
+        if !path.is_dir() {
+            anyhow::bail!(
+                "Module '{}': local source {:?} is not a directory",
+                module.name,
+                path
+            );
+        }
+        if module.r#ref.is_some() {
+            warn!(
+                "Module '{}': 'ref' is ignored for local sources",
+                module.name
+            );
+        }
+
+        // Local modules are edited in place, so the lock just records what was used
+        let checksum = format!("sha256:{}", content_hash(&path)?);
+        Ok(ResolvedModule {
+            lock: ModuleLock {
+                source: module.source.clone(),
+                r#ref: None,
+                path: subdir.map(str::to_string),
+                checksum,
+            },
+            path,
         })
     }
 
-    pub fn resolve(&self, source: &str, ref_: &str) -> anyhow::Result<PathBuf> {
-        // 1. Check Lockfile Integrity
-        if let Some(lock) = &self.lockfile {
-            if let Some(module_lock) = lock.modules.get(source) {
-                // Verify ref matches lock
-                if module_lock.r#ref != ref_ {
-                    // Start drift or update? Strict mode says abort?
-                    // For now, warn or error?
-                    // Lockfile Integrity Check rule: "System MUST abort ... if weaver.lock checksums do not match downloaded content"
-                    // Here we check ref match first.
+    fn resolve_git(
+        &self,
+        module: &ModuleConfig,
+        url: &str,
+        subdir: Option<&str>,
+    ) -> anyhow::Result<ResolvedModule> {
+        let ref_ = module.r#ref.as_deref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Module '{}' needs a 'ref' (tag, branch or commit) for git source '{}'",
+                module.name,
+                url
+            )
+        })?;
+
+        let (checkout, commit) = self.checkout(url, ref_)?;
+
+        // Lockfile Integrity Check rule: "System MUST abort ... if weaver.lock checksums do not match downloaded content"
+        if let Some(lock) = &self.lockfile
+            && let Some(module_lock) = lock.modules.get(&module.name)
+            && module_lock.source == url
+            && module_lock.r#ref.as_deref() == Some(ref_)
+            && module_lock.checksum != commit
+        {
+            anyhow::bail!(
+                "Module '{}': {}@{} resolved to commit {}, but weaver.lock expects {}. Remove its entry from weaver.lock to accept the new content.",
+                module.name,
+                url,
+                ref_,
+                commit,
+                module_lock.checksum
+            );
+        }
+
+        let path = match subdir {
+            Some(subdir) => {
+                let path = checkout.join(subdir);
+                if !path.is_dir() {
+                    anyhow::bail!(
+                        "Module '{}': '{}' not found in {}@{}",
+                        module.name,
+                        subdir,
+                        url,
+                        ref_
+                    );
                 }
+                path
             }
+            None => checkout,
+        };
+
+        Ok(ResolvedModule {
+            path,
+            lock: ModuleLock {
+                source: url.to_string(),
+                r#ref: Some(ref_.to_string()),
+                path: subdir.map(str::to_string),
+                checksum: commit,
+            },
+        })
+    }
+
+    /// Returns the cached clone of `url` at `ref_` and its commit, cloning it
+    /// on first use.
+    fn checkout(&self, url: &str, ref_: &str) -> anyhow::Result<(PathBuf, String)> {
+        let key = (url.to_string(), ref_.to_string());
+        if let Some(known) = self.checkouts.lock().unwrap().get(&key) {
+            return Ok(known.clone());
         }
 
-        let folder_name = urlencoding::encode(source);
+        let folder_name = urlencoding::encode(url);
         let path = self.cache_dir.join(folder_name.as_ref()).join(ref_);
 
         if !path.exists() {
             // Offline Fallback check:
             // "System MUST auto-fallback to the global cache with a warning if the upstream source is unreachable."
-            // Implicit: git clone handles network. If it fails, we can't fallback if the folder doesn't exist locally!
-            // Fallback implies "use what we have even if we can't update"?
-            // But if path !exists, we don't have it.
-            // So we try valid clone.
+            // A cached ref is used as is; without one we must clone, and a failed clone is fatal.
             std::fs::create_dir_all(&path)?;
-            if let Err(e) = git::clone(source, ref_, &path) {
-                // If clone fails and we don't have it, we assume we can't proceed.
-                // If we had it (path exists), we would skip clone.
-                // So fallback applies to "Create App from Module" -> Resolve Module.
-                // If module is already resolved (cached), we use it.
-                // We are checking `!path.exists()`. So if it exists, we stick to it (implicit offline use).
-                // If it doesn't exist, we MUST clone. If clone fails, we fail.
+            if let Err(e) = git::clone(url, ref_, &path) {
                 std::fs::remove_dir_all(&path).ok();
                 return Err(e);
             }
         }
 
-        Ok(path)
+        let commit = git::head_commit(&path)?;
+        self.checkouts
+            .lock()
+            .unwrap()
+            .insert(key, (path.clone(), commit.clone()));
+        Ok((path, commit))
+    }
+}
+
+/// `source[@ref][ under 'path']`, for error messages.
+fn describe_location(module: &ModuleConfig) -> String {
+    let mut location = module.source.clone();
+    if let (ModuleSource::Git(_), Some(r#ref)) =
+        (ModuleSource::parse(&module.source), &module.r#ref)
+    {
+        location.push_str(&format!("@{}", r#ref));
+    }
+    if let Some(path) = &module.path {
+        location.push_str(&format!(" under '{}'", path));
+    }
+    location
+}
+
+/// Hash of the relative paths and contents of all files under `dir`,
+/// ignoring `.git`.
+fn content_hash(dir: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    let walker = WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git");
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let rel = entry.path().strip_prefix(dir)?;
+            hasher.update(rel.to_string_lossy().replace('\\', "/").as_bytes());
+            hasher.update([0]);
+            hasher.update(std::fs::read(entry.path())?);
+            hasher.update([0]);
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_module(source: &str) -> ModuleConfig {
+        ModuleConfig {
+            name: "local".to_string(),
+            source: source.to_string(),
+            r#ref: None,
+            path: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            ModuleSource::parse("./modules/foo"),
+            ModuleSource::Local(PathBuf::from("./modules/foo"))
+        );
+        assert_eq!(
+            ModuleSource::parse("path:modules/foo"),
+            ModuleSource::Local(PathBuf::from("modules/foo"))
+        );
+        assert_eq!(
+            ModuleSource::parse("git@github.com:org/mod.git"),
+            ModuleSource::Git("git@github.com:org/mod.git".to_string())
+        );
+    }
+
+    #[test]
+    fn test_local_source_is_read_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("weaver.module.yaml"), "inputs: {}").unwrap();
+        let resolver = ModuleResolver::with_cache_dir(dir.path().join("store"), None);
+        let source = dir.path().to_string_lossy().to_string();
+
+        let first = resolver.resolve(&local_module(&source)).unwrap();
+        assert_eq!(first.path, dir.path());
+        assert!(first.lock.checksum.starts_with("sha256:"));
+        assert!(!dir.path().join("store").exists());
+
+        std::fs::write(
+            dir.path().join("weaver.module.yaml"),
+            "inputs: { a: {type: string} }",
+        )
+        .unwrap();
+        let second = resolver.resolve(&local_module(&source)).unwrap();
+        assert_ne!(first.lock.checksum, second.lock.checksum);
+    }
+
+    #[test]
+    fn test_git_source_needs_ref() {
+        let resolver = ModuleResolver::with_cache_dir(PathBuf::from("unused"), None);
+        let mut module = local_module("https://example.com/mod.git");
+        module.name = "remote".to_string();
+        let err = resolver.resolve(&module).err().unwrap().to_string();
+        assert!(err.contains("needs a 'ref'"), "{err}");
     }
 }
@@ -1,11 +1,13 @@
 use std::io::Write;
+use std::path::Path;
 use std::process::{Command, Stdio};
-use wasmtime::component::{Linker, ResourceTable, bindgen};
-use wasmtime::{Config, Engine};
+use wasmtime::component::{Component, Linker, ResourceTable, bindgen};
+use wasmtime::{Config, Engine, Store};
 
 bindgen!({
     world: "provider",
     path: "../../wit",
+    trappable_imports: true,
 });
 
 pub struct Host {
@@ -13,6 +15,12 @@ pub struct Host {
     pub table: ResourceTable,
 }
 
+impl Default for Host {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Host {
     pub fn new() -> Self {
         Self {
@@ -57,12 +65,11 @@ impl weaver::plugin::process::Host for Host {
             Err(e) => return Ok(Err(format!("Failed to spawn {}: {}", req.program, e))),
         };
 
-        if let Some(input) = &req.stdin {
-            if let Some(mut s) = child.stdin.take() {
-                if let Err(e) = s.write_all(input) {
-                    return Ok(Err(format!("Failed to write stdin: {}", e)));
-                }
-            }
+        if let Some(input) = &req.stdin
+            && let Some(mut s) = child.stdin.take()
+            && let Err(e) = s.write_all(input)
+        {
+            return Ok(Err(format!("Failed to write stdin: {}", e)));
         }
 
         let output = child
@@ -101,11 +108,31 @@ impl WasmPluginEngine {
         &self.engine
     }
 
-    // Helper to instantiate and run "get-secret" if we wanted to call it from here.
-    // For now, T022 manually calls plugins.
-    // We need to expose a way to get the Linker or instantiate.
-
     pub fn linker(&self) -> &Linker<Host> {
         &self.linker
     }
+
+    /// Calls `get-secret` of the plugin component at `plugin` for `key`.
+    pub fn get_secret(&self, plugin: &Path, key: &str) -> anyhow::Result<String> {
+        let component = Component::from_file(&self.engine, plugin)
+            .map_err(|e| anyhow::anyhow!("failed to load plugin {}: {}", plugin.display(), e))?;
+        let mut store = Store::new(&self.engine, Host::new());
+        let provider = Provider::instantiate(&mut store, &component, &self.linker)?;
+        let request = exports::weaver::plugin::secrets::SecretRequest {
+            key: key.to_string(),
+        };
+        // A trapping plugin is reported like any other failure
+        let result = provider
+            .weaver_plugin_secrets()
+            .call_get_secret(&mut store, &request)
+            .map_err(|e| anyhow::anyhow!("plugin {} trapped: {}", plugin.display(), e))?;
+        result.map_err(|e| {
+            use exports::weaver::plugin::secrets::SecretError;
+            match e {
+                SecretError::AccessDenied(msg) => anyhow::anyhow!("access denied: {}", msg),
+                SecretError::NotFound(msg) => anyhow::anyhow!("not found: {}", msg),
+                SecretError::Internal(msg) => anyhow::anyhow!("{}", msg),
+            }
+        })
+    }
 }
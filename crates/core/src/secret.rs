@@ -1,4 +1,9 @@
+use crate::config::SecretConfig;
+use crate::plugin::wasm::WasmPluginEngine;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 #[derive(Clone)]
 pub struct Secret<T>(T);
@@ -25,18 +30,165 @@ impl<T> fmt::Display for Secret<T> {
     }
 }
 
-pub struct SecretResolver;
+type EnvReader = dyn Fn(&str) -> Option<String>;
+
+/// Resolves the secrets declared under `secrets:` through their provider:
+/// `env` reads an environment variable, any other provider is a WASM plugin
+/// component `<provider>.wasm` found in one of the plugin folders. Each
+/// secret is resolved at most once per run.
+pub struct SecretResolver {
+    secrets: HashMap<String, SecretConfig>,
+    plugin_dirs: Vec<PathBuf>,
+    resolved: Mutex<HashMap<String, Secret<String>>>,
+    engine: OnceLock<WasmPluginEngine>,
+    /// Reads the variables of the `env` provider
+    env: Box<EnvReader>,
+}
 
 impl SecretResolver {
-    pub fn resolve(key: &str) -> anyhow::Result<Secret<String>> {
-        // Placeholder for MVP resolution
-        // 1. Try Env Var
-        if let Ok(v) = std::env::var(key) {
-            return Ok(Secret::new(v));
+    pub fn new(secrets: HashMap<String, SecretConfig>, plugin_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            secrets,
+            plugin_dirs,
+            resolved: Mutex::new(HashMap::new()),
+            engine: OnceLock::new(),
+            env: Box::new(|name| std::env::var(name).ok()),
+        }
+    }
+
+    /// Replaces the process environment read by the `env` provider.
+    pub fn with_env(mut self, env: impl Fn(&str) -> Option<String> + 'static) -> Self {
+        self.env = Box::new(env);
+        self
+    }
+
+    /// Resolver looking for plugins in the workspace's `.rw/plugins`, then
+    /// in `~/.rw/plugins`.
+    pub fn for_workspace(root: &Path, secrets: HashMap<String, SecretConfig>) -> Self {
+        let mut plugin_dirs = vec![root.join(".rw").join("plugins")];
+        if let Some(home) = home::home_dir() {
+            plugin_dirs.push(home.join(".rw").join("plugins"));
+        }
+        Self::new(secrets, plugin_dirs)
+    }
+
+    /// Value of the declared secret `name`. Fails when it is not declared or
+    /// its provider cannot produce it.
+    pub fn resolve(&self, name: &str) -> anyhow::Result<Secret<String>> {
+        if let Some(secret) = self.resolved.lock().unwrap().get(name) {
+            return Ok(secret.clone());
+        }
+        let config = self
+            .secrets
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("secret '{}' is not declared under 'secrets'", name))?;
+
+        let value = match config.provider.as_str() {
+            "env" => (self.env)(&config.key).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "secret '{}': environment variable '{}' is not set",
+                    name,
+                    config.key
+                )
+            })?,
+            provider => {
+                let file = format!("{}.wasm", provider);
+                let plugin = self
+                    .plugin_dirs
+                    .iter()
+                    .map(|dir| dir.join(&file))
+                    .find(|path| path.is_file())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "secret '{}': no provider '{}' (expected 'env' or a plugin {} in {})",
+                            name,
+                            provider,
+                            file,
+                            self.plugin_dirs
+                                .iter()
+                                .map(|d| d.display().to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })?;
+                let engine = match self.engine.get() {
+                    Some(engine) => engine,
+                    None => {
+                        let engine = WasmPluginEngine::new()?;
+                        self.engine.get_or_init(|| engine)
+                    }
+                };
+                engine.get_secret(&plugin, &config.key).map_err(|e| {
+                    anyhow::anyhow!("secret '{}' (provider '{}'): {}", name, provider, e)
+                })?
+            }
+        };
+
+        let secret = Secret::new(value);
+        self.resolved
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), secret.clone());
+        Ok(secret)
+    }
+
+    /// Resolves every declared secret, keyed by name.
+    pub fn resolve_all(&self) -> anyhow::Result<BTreeMap<String, Secret<String>>> {
+        let mut names: Vec<&String> = self.secrets.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| Ok((name.clone(), self.resolve(name)?)))
+            .collect()
+    }
+
+    /// `text` with the values of the secrets resolved so far replaced by `***`.
+    pub fn mask(&self, text: &str) -> String {
+        let resolved = self.resolved.lock().unwrap();
+        // Longer values first, so a secret containing another is fully masked
+        let mut values: Vec<&str> = resolved
+            .values()
+            .map(|s| s.expose().as_str())
+            .filter(|v| !v.is_empty())
+            .collect();
+        values.sort_by_key(|v| std::cmp::Reverse(v.len()));
+        let mut masked = text.to_string();
+        for value in values {
+            masked = masked.replace(value, "***");
         }
+        masked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver(provider: &str, key: &str) -> SecretResolver {
+        let config = SecretConfig {
+            provider: provider.to_string(),
+            key: key.to_string(),
+        };
+        SecretResolver::new([("token".to_string(), config)].into(), Vec::new())
+            .with_env(|name| (name == "RW_TEST_SECRET_TOKEN").then(|| "s3cr3t".to_string()))
+    }
+
+    #[test]
+    fn test_env_provider_and_masking() {
+        let resolver = resolver("env", "RW_TEST_SECRET_TOKEN");
+        assert_eq!(resolver.resolve("token").unwrap().expose(), "s3cr3t");
+        assert_eq!(resolver.mask("token=s3cr3t"), "token=***");
+        assert!(resolver.resolve("other").is_err());
+    }
 
-        // 2. Future: Call Wasm Plugins via WasmPluginEngine
+    #[test]
+    fn test_unresolved_secrets_fail() {
+        let err = resolver("env", "RW_TEST_SECRET_UNSET")
+            .resolve("token")
+            .unwrap_err();
+        assert!(err.to_string().contains("is not set"), "{err}");
 
-        Ok(Secret::new(format!("resolved-{}", key)))
+        let err = resolver("vault", "kv/token").resolve_all().unwrap_err();
+        assert!(err.to_string().contains("no provider 'vault'"), "{err}");
     }
 }
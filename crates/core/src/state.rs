@@ -1,14 +1,26 @@
+use crate::migrate::{self, DocumentKind};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct State {
+    #[serde(default)]
+    pub version: String,
     pub files: HashMap<PathBuf, FileState>,
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            version: migrate::STATE_VERSION.to_string(),
+            files: HashMap::new(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileState {
     pub checksum: String,
@@ -19,7 +31,11 @@ impl State {
     pub fn load(path: &Path) -> anyhow::Result<Self> {
         if path.exists() {
             let content = fs::read_to_string(path)?;
-            let state = serde_yml::from_str(&content)?;
+            // The state file is owned by rw, so older layouts are upgraded on the fly
+            let mut doc: serde_yml::Value = serde_yml::from_str(&content)?;
+            migrate::migrate(DocumentKind::State, &mut doc)
+                .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+            let state = serde_yml::from_value(doc)?;
             Ok(state)
         } else {
             Ok(Self::default())
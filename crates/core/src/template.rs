@@ -1,18 +1,355 @@
+//! Rendering of module templates.
+//!
+//! Templates are rendered with:
+//! * each input of the app layer under its own name, and all of them under `inputs`,
+//! * `app.name` and `app.path`,
+//! * `module.name`, `module.source`, `module.ref`, `module.path` and `module.block`,
+//! * `module_path`: the folder the module was read from,
+//! * `workspace_root`: the absolute path of the workspace,
+//! * `secrets.<name>`: every secret declared in `weaver.yaml`,
+//! * `rw_version`: the version of rw.
+
+use crate::app::App;
+use crate::config::ModuleConfig;
+use crate::filters;
+use crate::input;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use tera::{Context, Tera};
+use walkdir::WalkDir;
+
+/// Names the template context defines besides the inputs. Inputs must not
+/// use them, nor start with `rw_`.
+pub const RESERVED_NAMES: &[&str] = &[
+    "app",
+    "inputs",
+    "module",
+    "module_path",
+    "output",
+    "secrets",
+    "workspace_root",
+];
+
+/// Whether `name` may not be used for an input.
+pub fn is_reserved(name: &str) -> bool {
+    name.starts_with("rw_") || RESERVED_NAMES.contains(&name)
+}
 
+#[derive(Serialize)]
+struct AppInfo<'a> {
+    name: &'a str,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct ModuleInfo<'a> {
+    name: &'a str,
+    source: &'a str,
+    r#ref: Option<&'a str>,
+    path: Option<&'a str>,
+    block: Option<&'a str>,
+}
+
+/// Everything a template of one app layer is rendered with.
+pub struct TemplateContext<'a> {
+    pub app: &'a App,
+    pub module: &'a ModuleConfig,
+    /// Block of the module, when the layer references one
+    pub block: Option<&'a str>,
+    /// Folder the module was read from
+    pub module_path: &'a Path,
+    pub workspace_root: &'a Path,
+    pub secrets: &'a BTreeMap<String, String>,
+}
+
+impl TemplateContext<'_> {
+    pub fn build(&self) -> anyhow::Result<Context> {
+        let mut inputs: HashMap<&str, serde_yml::Value> = self
+            .app
+            .inputs
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect();
+        for (key, secret) in &self.app.secrets {
+            inputs.insert(key, secret.expose().clone());
+        }
+
+        let mut context = Context::from_serialize(&inputs)?;
+        context.insert("inputs", &inputs);
+        context.insert(
+            "app",
+            &AppInfo {
+                name: &self.app.name,
+                path: self.app.path.to_string_lossy().replace('\\', "/"),
+            },
+        );
+        context.insert(
+            "module",
+            &ModuleInfo {
+                name: &self.module.name,
+                source: &self.module.source,
+                r#ref: self.module.r#ref.as_deref(),
+                path: self.module.path.as_deref(),
+                block: self.block,
+            },
+        );
+        context.insert("module_path", &self.module_path.to_string_lossy());
+        context.insert("workspace_root", &self.workspace_root.to_string_lossy());
+        context.insert("secrets", self.secrets);
+        context.insert("rw_version", env!("CARGO_PKG_VERSION"));
+        Ok(context)
+    }
+}
+
+/// Folders under a module's `templates/` holding shared fragments: they can
+/// be included or imported by other templates but are never emitted.
+pub const SHARED_DIRS: &[&str] = &["_partials", "_macros"];
+
+/// Whether the template at `rel_path` (relative to `templates/`) is only
+/// shared with other templates.
+pub fn is_shared(rel_path: &Path) -> bool {
+    rel_path
+        .components()
+        .next()
+        .is_some_and(|first| SHARED_DIRS.iter().any(|dir| first.as_os_str() == *dir))
+}
+
+/// The templates of one `templates/` folder, compiled together.
+struct TemplateSet {
+    tera: Tera,
+    /// Sources by template name, to locate render errors
+    sources: HashMap<String, String>,
+}
+
+/// Renders module templates. Each `templates/` folder is compiled once, with
+/// templates named by their path relative to it, so they can
+/// `{% include "_partials/x.j2" %}`, `{% import "_macros/x.j2" as x %}` or
+/// `{% extends %}` one another.
 pub struct TemplateEngine {
-    _tera: Tera,
+    sets: HashMap<PathBuf, TemplateSet>,
 }
 
 impl TemplateEngine {
     pub fn new() -> anyhow::Result<Self> {
-        let tera = Tera::default();
-        Ok(Self { _tera: tera })
+        Ok(Self {
+            sets: HashMap::new(),
+        })
     }
 
-    pub fn render(&self, template_str: &str, context: &Context) -> anyhow::Result<String> {
-        // Use one_off for ad-hoc templates to avoid mutability requirement
-        // TODO: Optimize if performance becomes an issue
-        Ok(Tera::one_off(template_str, context, false)?)
+    /// Compiles every template under `dir`, unless already done. Errors are
+    /// reported against `origin`, the folder as shown to users.
+    pub fn load(&mut self, dir: &Path, origin: &str) -> anyhow::Result<()> {
+        if self.sets.contains_key(dir) {
+            return Ok(());
+        }
+        let mut sources = HashMap::new();
+        if dir.is_dir() {
+            for entry in WalkDir::new(dir).sort_by_file_name() {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let name = template_name(entry.path().strip_prefix(dir)?);
+                let source = std::fs::read_to_string(entry.path())
+                    .map_err(|e| anyhow::anyhow!("{}/{}: {}", origin, name, e))?;
+                sources.insert(name, source);
+            }
+        }
+
+        let mut tera = new_tera();
+        tera.add_raw_templates(sources.iter().map(|(n, s)| (n.as_str(), s.as_str())))
+            .map_err(|e| {
+                static PARSED: std::sync::LazyLock<Regex> =
+                    std::sync::LazyLock::new(|| Regex::new(r"^Failed to parse '(.+)'").unwrap());
+                let failed = PARSED
+                    .captures(&e.to_string())
+                    .map(|c| c[1].to_string())
+                    .unwrap_or_default();
+                let source = sources.get(&failed).map(String::as_str).unwrap_or_default();
+                render_error(&format!("{}/{}", origin, failed), source, &e)
+            })?;
+        self.sets
+            .insert(dir.to_path_buf(), TemplateSet { tera, sources });
+        Ok(())
+    }
+
+    /// Renders the template at `rel_path` of the folder `dir`, which must
+    /// have been loaded. Errors are prefixed with `origin` and, when it can be
+    /// found, the line of the template at fault (`origin:line: ...`).
+    pub fn render_file(
+        &self,
+        dir: &Path,
+        rel_path: &Path,
+        origin: &str,
+        context: &Context,
+    ) -> anyhow::Result<String> {
+        let set = self
+            .sets
+            .get(dir)
+            .ok_or_else(|| anyhow::anyhow!("{}: templates were not loaded", origin))?;
+        let name = template_name(rel_path);
+        set.tera.render(&name, context).map_err(|e| {
+            let source = set.sources.get(&name).map(String::as_str);
+            render_error(origin, source.unwrap_or_default(), &e)
+        })
+    }
+
+    /// Renders the ad-hoc template `template_str`; errors are prefixed with
+    /// `name` and the line at fault, as for [`TemplateEngine::render_file`].
+    pub fn render(
+        &self,
+        name: &str,
+        template_str: &str,
+        context: &Context,
+    ) -> anyhow::Result<String> {
+        new_tera()
+            .render_str(template_str, context)
+            .map_err(|e| render_error(name, template_str, &e))
+    }
+
+    /// Evaluates a `when` condition, a bare expression (`publish`) or a
+    /// template, as for inputs.
+    pub fn condition(&self, name: &str, when: &str, context: &Context) -> anyhow::Result<bool> {
+        let rendered = self.render(name, &input::when_template(when), context)?;
+        input::when_result(&rendered).map_err(|e| anyhow::anyhow!("{}: {}", name, e))
+    }
+}
+
+/// Tera with the filters of [`crate::filters`].
+pub(crate) fn new_tera() -> Tera {
+    let mut tera = Tera::default();
+    // Generated files are not HTML, whatever their extension
+    tera.autoescape_on(Vec::new());
+    filters::register(&mut tera);
+    tera
+}
+
+/// Name of a template in its set: its relative path, with `/` separators.
+fn template_name(rel_path: &Path) -> String {
+    rel_path.to_string_lossy().replace('\\', "/")
+}
+
+/// Tera reports syntax errors with a `--> line:column` marker but runtime
+/// errors (e.g. an unknown variable) without a position; those are located
+/// at the first tag naming the variable or filter involved.
+fn render_error(name: &str, template: &str, error: &tera::Error) -> anyhow::Error {
+    static POSITION: std::sync::LazyLock<Regex> =
+        std::sync::LazyLock::new(|| Regex::new(r"--> (\d+):(\d+)").unwrap());
+    static NAMED: std::sync::LazyLock<Regex> =
+        std::sync::LazyLock::new(|| Regex::new(r"`([^`]+)`").unwrap());
+
+    let mut messages = vec![error.to_string()];
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        messages.push(cause.to_string());
+        source = cause.source();
+    }
+    // The outer message only repeats the one-off template name
+    let detail = messages.last().cloned().unwrap_or_default();
+
+    if let Some(position) = POSITION.captures(&detail) {
+        let line = &position[1];
+        let message = detail
+            .lines()
+            .rev()
+            .find(|l| !l.trim().is_empty())
+            .unwrap_or_default()
+            .trim_start_matches(['=', ' '])
+            .to_string();
+        return anyhow::anyhow!("{}:{}: {}", name, line, message);
+    }
+
+    let line = NAMED.captures(&detail).and_then(|named| {
+        let needle = &named[1];
+        template
+            .lines()
+            .position(|line| (line.contains("{{") || line.contains("{%")) && line.contains(needle))
+    });
+    match line {
+        Some(idx) => anyhow::anyhow!("{}:{}: {}", name, idx + 1, detail),
+        None => anyhow::anyhow!("{}: {}", name, detail),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_errors_name_file_and_line() {
+        let engine = TemplateEngine::new().unwrap();
+        let mut context = Context::new();
+        context.insert("name", "api");
+
+        let err = engine
+            .render(
+                "templates/a.j2",
+                "name={{ name }}\nbucket={{ bucket }}\n",
+                &context,
+            )
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("templates/a.j2:2: "), "{err}");
+        assert!(err.contains("`bucket` not found"), "{err}");
+
+        let err = engine
+            .render("templates/b.j2", "ok\n{{ name | }}\nrest\n", &context)
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("templates/b.j2:2: "), "{err}");
+    }
+
+    #[test]
+    fn test_module_templates_share_partials_and_macros() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates = dir.path().join("templates");
+        std::fs::create_dir_all(templates.join("_partials")).unwrap();
+        std::fs::create_dir_all(templates.join("_macros")).unwrap();
+        std::fs::write(
+            templates.join("_partials/header.j2"),
+            "# managed by rw for {{ name }}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            templates.join("_macros/env.j2"),
+            "{% macro var(key, value) %}{{ key | upper }}={{ value }}{% endmacro var %}",
+        )
+        .unwrap();
+        std::fs::write(
+            templates.join("app.env.j2"),
+            "{% import \"_macros/env.j2\" as env %}{% include \"_partials/header.j2\" %}{{ env::var(key=\"name\", value=name) }}\n",
+        )
+        .unwrap();
+
+        let mut engine = TemplateEngine::new().unwrap();
+        engine.load(&templates, "svc/templates").unwrap();
+        let mut context = Context::new();
+        context.insert("name", "api");
+        let rendered = engine
+            .render_file(
+                &templates,
+                Path::new("app.env.j2"),
+                "svc/templates/app.env.j2",
+                &context,
+            )
+            .unwrap();
+        assert_eq!(rendered, "# managed by rw for api\nNAME=api\n");
+
+        assert!(is_shared(Path::new("_partials/header.j2")));
+        assert!(!is_shared(Path::new("app.env.j2")));
+
+        // Syntax errors are reported when the folder is compiled
+        std::fs::write(templates.join("_partials/header.j2"), "ok\n{{ name | }}\n").unwrap();
+        let err = TemplateEngine::new()
+            .unwrap()
+            .load(&templates, "svc/templates")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("svc/templates/_partials/header.j2:2: "),
+            "{err}"
+        );
     }
 }
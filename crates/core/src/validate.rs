@@ -0,0 +1,712 @@
+//! Static checks of a workspace config, reported as located diagnostics.
+//!
+//! Syntax and type errors come with the position reported by the YAML
+//! parser. Semantic problems (unknown modules, overlapping app paths,
+//! undeclared inputs, ...) are located by searching the file that declared
+//! the offending app, which is exact for the usual one-key-per-line layout.
+
+use crate::config::{AppConfig, AppLayer, ExtendsEntry, ModuleManifest, WeaverConfig};
+use crate::input::InputSpec;
+use crate::interpolate;
+use crate::loader;
+use crate::module::ModuleResolver;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: PathBuf,
+    /// 1-based line, when the problem could be located
+    pub line: Option<usize>,
+    /// 1-based column, when the problem could be located
+    pub column: Option<usize>,
+    pub message: String,
+    /// Source line at `line`
+    pub excerpt: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        writeln!(f, "{}: {}", label, self.message)?;
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                writeln!(f, "  --> {}:{}:{}", self.file.display(), line, column)?
+            }
+            (Some(line), None) => writeln!(f, "  --> {}:{}", self.file.display(), line)?,
+            _ => writeln!(f, "  --> {}", self.file.display())?,
+        }
+        if let (Some(line), Some(excerpt)) = (self.line, &self.excerpt) {
+            let gutter = " ".repeat(line.to_string().len());
+            writeln!(f, "{} |", gutter)?;
+            writeln!(f, "{} | {}", line, excerpt)?;
+            if let Some(column) = self.column {
+                writeln!(f, "{} | {}^", gutter, " ".repeat(column.saturating_sub(1)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidateOptions {
+    /// Report required inputs that neither the config nor the manifest
+    /// default provides. Disabled before plan/apply, where they are prompted.
+    pub require_inputs: bool,
+}
+
+impl Default for ValidateOptions {
+    fn default() -> Self {
+        Self {
+            require_inputs: true,
+        }
+    }
+}
+
+/// Validates the workspace rooted at `config_path`. Module manifests are
+/// resolved through `resolver` to check app inputs.
+pub fn validate_workspace(
+    config_path: &Path,
+    resolver: &ModuleResolver,
+    options: &ValidateOptions,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    // A root file that doesn't parse can't list its fragments, check it alone
+    let (files, listing_error) = match loader::config_files(config_path) {
+        Ok(files) => (files, None),
+        Err(e) => (vec![config_path.to_path_buf()], Some(e)),
+    };
+
+    // 1. Syntax, per file
+    let mut sources = HashMap::new();
+    for path in files {
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                diagnostics.push(unlocated(&path, e.to_string()));
+                continue;
+            }
+        };
+        let source = SourceFile::new(path.clone(), content);
+        match serde_yml::from_str::<serde_yml::Value>(&source.content) {
+            Ok(doc) => diagnostics.extend(duplicate_names(&source, &doc)),
+            Err(e) => {
+                let (line, column) = e
+                    .location()
+                    .map(|loc| (loc.line(), loc.column()))
+                    .unwrap_or((0, 0));
+                diagnostics.push(source.at(
+                    Severity::Error,
+                    line.checked_sub(1),
+                    column.saturating_sub(1),
+                    e.to_string(),
+                ));
+            }
+        }
+        sources.insert(path, source);
+    }
+    if let Some(e) = listing_error
+        && diagnostics.is_empty()
+    {
+        diagnostics.push(unlocated(config_path, e.to_string()));
+    }
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return diagnostics;
+    }
+
+    // 2. Merge and shape
+    let config = match WeaverConfig::load(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            let root = sources
+                .get(config_path)
+                .map(|source| source.at(Severity::Error, None, 0, format!("{:#}", e)));
+            diagnostics.push(root.unwrap_or_else(|| unlocated(config_path, format!("{:#}", e))));
+            return diagnostics;
+        }
+    };
+
+    // 3. Semantics
+    let checker = Checker {
+        config: &config,
+        sources: &sources,
+        root: config_path,
+    };
+    diagnostics.extend(checker.check_apps(resolver, options));
+    diagnostics
+}
+
+fn unlocated(file: &Path, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        file: file.to_path_buf(),
+        line: None,
+        column: None,
+        message,
+        excerpt: None,
+    }
+}
+
+/// Names declared twice in the same file are almost always a copy/paste
+/// mistake; across files they are merged on purpose.
+fn duplicate_names(source: &SourceFile, doc: &serde_yml::Value) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for section in ["apps", "modules"] {
+        let Some(entries) = doc.get(section).and_then(|v| v.as_sequence()) else {
+            continue;
+        };
+        let mut seen = HashSet::new();
+        let mut search_from = 0;
+        for name in entries
+            .iter()
+            .filter_map(|entry| entry.get("name").and_then(|n| n.as_str()))
+        {
+            let found = source.find_entry(search_from, name);
+            if let Some((line, _)) = found {
+                search_from = line + 1;
+            }
+            if !seen.insert(name) {
+                let (line, column) = found.map_or((None, 0), |(l, c)| (Some(l), c));
+                diagnostics.push(source.at(
+                    Severity::Error,
+                    line,
+                    column,
+                    format!(
+                        "Duplicate {} name '{}'",
+                        section.trim_end_matches('s'),
+                        name
+                    ),
+                ));
+            }
+        }
+    }
+    diagnostics
+}
+
+struct Checker<'a> {
+    config: &'a WeaverConfig,
+    sources: &'a HashMap<PathBuf, SourceFile>,
+    root: &'a Path,
+}
+
+impl Checker<'_> {
+    fn source_of(&self, app: &AppConfig) -> Option<&SourceFile> {
+        let origin = app.origin.as_deref().unwrap_or(self.root);
+        self.sources.get(origin)
+    }
+
+    /// Diagnostic at `key` (or `value` within that line, when given) inside
+    /// the declaration of `app`.
+    fn at_app(
+        &self,
+        app: &AppConfig,
+        key: Option<&str>,
+        value: Option<&str>,
+        severity: Severity,
+        message: String,
+    ) -> Diagnostic {
+        let Some(source) = self.source_of(app) else {
+            return Diagnostic {
+                severity,
+                ..unlocated(app.origin.as_deref().unwrap_or(self.root), message)
+            };
+        };
+        // App files hold a single app without a list entry to search for
+        let start = source.find_entry(0, &app.name).unwrap_or((0, 0));
+        let mut position = Some(start);
+        if let Some(key) = key {
+            position = source.find_key(start.0, key).or(position);
+        }
+        if let Some(value) = value
+            && let Some(found) = source.find_word(position.map_or(start.0, |p| p.0), value)
+        {
+            position = Some(found);
+        }
+        let (line, column) = position.unwrap_or(start);
+        source.at(severity, Some(line), column, message)
+    }
+
+    fn check_apps(&self, resolver: &ModuleResolver, options: &ValidateOptions) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut manifests: HashMap<String, Option<ModuleManifest>> = HashMap::new();
+
+        for app in &self.config.apps {
+            diagnostics.extend(self.check_path(app));
+
+            let layers = match app.layers() {
+                Ok(layers) => layers,
+                Err(e) => {
+                    diagnostics.push(self.at_app(
+                        app,
+                        Some("extends"),
+                        None,
+                        Severity::Error,
+                        e.to_string(),
+                    ));
+                    continue;
+                }
+            };
+            if layers.is_empty() {
+                diagnostics.push(self.at_app(
+                    app,
+                    None,
+                    None,
+                    Severity::Error,
+                    format!("App '{}' must declare 'module' or 'extends'", app.name),
+                ));
+            }
+
+            // Manifest of each layer, `None` when it could not be loaded
+            let mut layer_manifests = Vec::new();
+            for layer in &layers {
+                let key = if app.module.as_deref() == Some(layer.reference.as_str()) {
+                    "module"
+                } else {
+                    "extends"
+                };
+                let module = match self.config.find_module(&layer.reference) {
+                    Ok((module, _)) => module,
+                    Err(e) => {
+                        diagnostics.push(self.at_app(
+                            app,
+                            Some(key),
+                            Some(&layer.reference),
+                            Severity::Error,
+                            format!("App '{}': {}", app.name, e),
+                        ));
+                        layer_manifests.push(None);
+                        continue;
+                    }
+                };
+
+                let manifest = manifests.entry(module.name.clone()).or_insert_with(|| {
+                    let loaded = resolver.resolve(module).and_then(|resolved| {
+                        ModuleManifest::load(&resolved.path.join("weaver.module.yaml"))
+                    });
+                    match loaded {
+                        Ok(manifest) => Some(manifest),
+                        Err(e) => {
+                            diagnostics.push(self.at_module(
+                                &module.name,
+                                format!("Module '{}' could not be loaded: {:#}", module.name, e),
+                            ));
+                            None
+                        }
+                    }
+                });
+                layer_manifests.push(manifest.clone());
+            }
+
+            diagnostics.extend(self.check_inputs(app, &layers, &layer_manifests, options));
+        }
+
+        diagnostics.extend(self.check_overlaps());
+        diagnostics
+    }
+
+    fn check_inputs(
+        &self,
+        app: &AppConfig,
+        layers: &[AppLayer],
+        manifests: &[Option<ModuleManifest>],
+        options: &ValidateOptions,
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        // Shared inputs only need to be declared by one of the app's modules
+        if manifests.iter().all(Option::is_some) {
+            let mut shared: Vec<_> = app.inputs.keys().collect();
+            shared.sort();
+            for input in shared {
+                if !manifests
+                    .iter()
+                    .flatten()
+                    .any(|m| m.inputs.contains_key(input))
+                {
+                    diagnostics.push(self.at_app(
+                        app,
+                        Some(input),
+                        None,
+                        Severity::Error,
+                        format!(
+                            "App '{}': input '{}' is not declared by any of its modules",
+                            app.name, input
+                        ),
+                    ));
+                }
+            }
+        }
+
+        // Inputs scoped to one `extends` entry must be declared by that module
+        for entry in &app.extends {
+            let ExtendsEntry::Detailed { module, inputs } = entry else {
+                continue;
+            };
+            let Some(idx) = layers.iter().position(|l| &l.reference == module) else {
+                continue;
+            };
+            let Some(manifest) = &manifests[idx] else {
+                continue;
+            };
+            let mut keys: Vec<_> = inputs.keys().collect();
+            keys.sort();
+            for input in keys {
+                if !manifest.inputs.contains_key(input) {
+                    diagnostics.push(self.at_app(
+                        app,
+                        Some(input),
+                        None,
+                        Severity::Error,
+                        format!(
+                            "App '{}': input '{}' is not declared by module '{}'",
+                            app.name, input, module
+                        ),
+                    ));
+                }
+            }
+        }
+
+        // Explicit values must match the declared types; secrets are only
+        // known when the app is instantiated
+        for (layer, manifest) in layers.iter().zip(manifests) {
+            let Some(manifest) = manifest else {
+                continue;
+            };
+            let mut keys: Vec<_> = layer.inputs.keys().collect();
+            keys.sort();
+            for key in keys {
+                let value = &layer.inputs[key];
+                let Some(def) = manifest.inputs.get(key) else {
+                    continue;
+                };
+                if interpolate::contains_secret(value) {
+                    continue;
+                }
+                if let Err(e) = InputSpec::of(def).and_then(|spec| spec.check(value)) {
+                    diagnostics.push(self.at_app(
+                        app,
+                        Some(key),
+                        None,
+                        Severity::Error,
+                        format!(
+                            "App '{}': input '{}' of module '{}': {}",
+                            app.name, key, layer.reference, e
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if options.require_inputs {
+            for (layer, manifest) in layers.iter().zip(manifests) {
+                let Some(manifest) = manifest else {
+                    continue;
+                };
+                let mut missing: Vec<_> = manifest
+                    .inputs
+                    .iter()
+                    .filter(|(key, def)| {
+                        // Conditional inputs may turn out not to be needed
+                        def.required
+                            && def.default.is_none()
+                            && def.when.is_none()
+                            && def.from_command.is_empty()
+                            && !layer.inputs.contains_key(*key)
+                    })
+                    .map(|(key, _)| key)
+                    .collect();
+                missing.sort();
+                for input in missing {
+                    diagnostics.push(self.at_app(
+                        app,
+                        Some("inputs"),
+                        None,
+                        Severity::Error,
+                        format!(
+                            "App '{}': missing required input '{}' for module '{}'",
+                            app.name, input, layer.reference
+                        ),
+                    ));
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    /// App paths must stay inside the workspace.
+    fn check_path(&self, app: &AppConfig) -> Option<Diagnostic> {
+        let path = Path::new(&app.path);
+        let mut depth: i32 = 0;
+        for component in path.components() {
+            match component {
+                Component::Prefix(_) | Component::RootDir => {
+                    depth = -1;
+                    break;
+                }
+                Component::ParentDir => depth -= 1,
+                Component::Normal(_) => depth += 1,
+                Component::CurDir => {}
+            }
+            if depth < 0 {
+                break;
+            }
+        }
+        (depth < 0).then(|| {
+            self.at_app(
+                app,
+                Some("path"),
+                None,
+                Severity::Error,
+                format!(
+                    "App '{}': path '{}' is outside the workspace",
+                    app.name, app.path
+                ),
+            )
+        })
+    }
+
+    /// Two apps in the same folder would fight over the same files. Nested
+    /// folders are legitimate (e.g. a root app next to service apps) but
+    /// worth a warning.
+    fn check_overlaps(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let normalized: Vec<_> = self
+            .config
+            .apps
+            .iter()
+            .map(|app| normalize(&app.path))
+            .collect();
+
+        for (i, app) in self.config.apps.iter().enumerate() {
+            for (j, other) in self.config.apps.iter().enumerate().take(i) {
+                let (a, b) = (&normalized[i], &normalized[j]);
+                if a == b {
+                    diagnostics.push(self.at_app(
+                        app,
+                        Some("path"),
+                        None,
+                        Severity::Error,
+                        format!(
+                            "App '{}' uses the same path as app '{}' ('{}')",
+                            app.name, other.name, app.path
+                        ),
+                    ));
+                } else if a.starts_with(b) || b.starts_with(a) {
+                    diagnostics.push(self.at_app(
+                        app,
+                        Some("path"),
+                        None,
+                        Severity::Warning,
+                        format!(
+                            "App '{}' ('{}') overlaps app '{}' ('{}')",
+                            app.name, app.path, other.name, other.path
+                        ),
+                    ));
+                }
+            }
+        }
+        diagnostics
+    }
+
+    fn at_module(&self, name: &str, message: String) -> Diagnostic {
+        for path in &self.config.sources {
+            if let Some(source) = self.sources.get(path)
+                && let Some((line, column)) = source.find_entry(0, name)
+            {
+                return source.at(Severity::Error, Some(line), column, message);
+            }
+        }
+        unlocated(self.root, message)
+    }
+}
+
+fn normalize(path: &str) -> PathBuf {
+    Path::new(path)
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+/// A config file kept in memory to locate diagnostics.
+struct SourceFile {
+    path: PathBuf,
+    content: String,
+    lines: Vec<String>,
+}
+
+impl SourceFile {
+    fn new(path: PathBuf, content: String) -> Self {
+        let lines = content.lines().map(str::to_string).collect();
+        Self {
+            path,
+            content,
+            lines,
+        }
+    }
+
+    /// `line` and `column` are 0-based.
+    fn at(
+        &self,
+        severity: Severity,
+        line: Option<usize>,
+        column: usize,
+        message: String,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity,
+            file: self.path.clone(),
+            line: line.map(|l| l + 1),
+            column: line.map(|_| column + 1),
+            message,
+            excerpt: line.and_then(|l| self.lines.get(l).cloned()),
+        }
+    }
+
+    fn find(&self, from: usize, re: &Regex, group: usize) -> Option<(usize, usize)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(from)
+            .find_map(|(idx, line)| {
+                re.captures(line)
+                    .and_then(|c| c.get(group))
+                    .map(|m| (idx, m.start()))
+            })
+    }
+
+    /// Position of the `name: <name>` entry of a list item.
+    fn find_entry(&self, from: usize, name: &str) -> Option<(usize, usize)> {
+        let re = Regex::new(&format!(
+            r#"(?:^|[\s{{,-])(name\s*:\s*["']?{}["']?)\s*(?:$|[,}}#])"#,
+            regex::escape(name)
+        ))
+        .ok()?;
+        self.find(from, &re, 1)
+    }
+
+    /// Position of `key:` at or after line `from`.
+    fn find_key(&self, from: usize, key: &str) -> Option<(usize, usize)> {
+        let re = Regex::new(&format!(
+            r#"(?:^|[\s{{,])(["']?{}["']?\s*:)"#,
+            regex::escape(key)
+        ))
+        .ok()?;
+        self.find(from, &re, 1)
+    }
+
+    /// Position of `word` (not part of a longer name) at or after line `from`.
+    fn find_word(&self, from: usize, word: &str) -> Option<(usize, usize)> {
+        let re = Regex::new(&format!(
+            r#"(?:^|[^\w.-])({})(?:$|[^\w.-])"#,
+            regex::escape(word)
+        ))
+        .ok()?;
+        self.find(from, &re, 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(files: &[(&str, &str)]) -> Vec<Diagnostic> {
+        let dir = tempfile::tempdir().unwrap();
+        for (rel, content) in files {
+            let path = dir.path().join(rel);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let resolver = ModuleResolver::with_cache_dir(dir.path().join("store"), None);
+        validate_workspace(
+            &dir.path().join("weaver.yaml"),
+            &resolver,
+            &ValidateOptions::default(),
+        )
+    }
+
+    #[test]
+    fn test_syntax_error_is_located() {
+        let diagnostics = validate(&[("weaver.yaml", "version: \"1\"\napps: [\n")]);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].line.is_some());
+    }
+
+    #[test]
+    fn test_semantic_errors_are_located() {
+        let diagnostics = validate(&[(
+            "weaver.yaml",
+            r#"version: "1"
+modules: []
+apps:
+  - name: api
+    module: missing
+    path: api
+  - name: web
+    module: missing
+    path: ../web
+  - name: api
+    module: missing
+    path: api
+"#,
+        )]);
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert_eq!(diagnostics[0].message, "Duplicate app name 'api'");
+        assert_eq!(diagnostics[0].line, Some(10));
+        assert_eq!(diagnostics[0].column, Some(5));
+
+        let diagnostics = validate(&[(
+            "weaver.yaml",
+            r#"version: "1"
+modules: []
+apps:
+  - name: api
+    module: missing
+    path: api
+  - name: web
+    extends: [missing]
+    path: ../web
+  - name: dup
+    module: missing
+    path: ./api
+"#,
+        )]);
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.message.as_str()))
+            .collect();
+        assert!(messages.contains(&(Some(5), Some(13), "App 'api': Module 'missing' not found")));
+        assert!(messages.contains(&(Some(8), Some(15), "App 'web': Module 'missing' not found")));
+        assert!(messages.contains(&(
+            Some(9),
+            Some(5),
+            "App 'web': path '../web' is outside the workspace"
+        )));
+        assert!(messages.contains(&(
+            Some(12),
+            Some(5),
+            "App 'dup' uses the same path as app 'api' ('./api')"
+        )));
+    }
+}
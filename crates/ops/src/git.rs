@@ -29,3 +29,17 @@ pub fn clone(url: &str, ref_: &str, dest: &Path) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Commit SHA checked out in `repo`.
+pub fn head_commit(repo: &Path) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(repo)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("Git rev-parse failed in {:?}", repo));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}